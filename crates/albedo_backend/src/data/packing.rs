@@ -17,3 +17,37 @@ impl Uint24_8 {
         ((self.0 & 0xFF000000) >> 24) as u8
     }
 }
+
+/// Encode a unit vector into 32 bits using an octahedral mapping.
+///
+/// Each component is stored on 16 bits. This matches `packOctahedral`
+/// in the shaders.
+pub fn pack_octahedral(v: [f32; 3]) -> u32 {
+    let l1_norm = v[0].abs() + v[1].abs() + v[2].abs();
+    if l1_norm <= 0.0 {
+        return 0;
+    }
+    let mut p = [v[0] / l1_norm, v[1] / l1_norm];
+    if v[2] < 0.0 {
+        let sign = |x: f32| if x >= 0.0 { 1.0 } else { -1.0 };
+        p = [
+            (1.0 - p[1].abs()) * sign(p[0]),
+            (1.0 - p[0].abs()) * sign(p[1]),
+        ];
+    }
+    let x = ((p[0] * 0.5 + 0.5).clamp(0.0, 1.0) * 65535.0).round() as u32;
+    let y = ((p[1] * 0.5 + 0.5).clamp(0.0, 1.0) * 65535.0).round() as u32;
+    x | (y << 16)
+}
+
+/// Decode a unit vector encoded with [`pack_octahedral`].
+pub fn unpack_octahedral(packed: u32) -> [f32; 3] {
+    let x = (packed & 0xFFFF) as f32 / 65535.0 * 2.0 - 1.0;
+    let y = (packed >> 16) as f32 / 65535.0 * 2.0 - 1.0;
+    let z = 1.0 - x.abs() - y.abs();
+    let t = f32::max(0.0, -z);
+    let x = if x >= 0.0 { x - t } else { x + t };
+    let y = if y >= 0.0 { y - t } else { y + t };
+    let len = (x * x + y * y + z * z).sqrt();
+    [x / len, y / len, z / len]
+}
@@ -1,4 +1,4 @@
-use guillotiere::{size2, Allocation, AtlasAllocator};
+use guillotiere::{size2, Allocation, AllocatorOptions, AtlasAllocator};
 
 use crate::data::packing::Uint24_8;
 
@@ -7,7 +7,8 @@ use crate::data::packing::Uint24_8;
 pub struct TextureBlock {
     x: u32,
     y: u32,
-    width: u32,
+    // Width should be stored in the first 24 bits, and mip count on the last 8 bits.
+    width_and_mips: Uint24_8,
     // Height should be stored in the first 24 bits, and atlas on the last 8 bits.
     height_and_layer: Uint24_8,
 }
@@ -26,10 +27,22 @@ impl TextureBlock {
     }
 
     pub fn new(layer: u8, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self::with_mips(layer, x, y, width, height, 1)
+    }
+
+    /// Create a block sampled with `mip_level_count` levels.
+    pub fn with_mips(
+        layer: u8,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        mip_level_count: u8,
+    ) -> Self {
         Self {
             x,
             y,
-            width,
+            width_and_mips: Uint24_8::new(width, mip_level_count),
             height_and_layer: Uint24_8::new(height, layer),
         }
     }
@@ -41,7 +54,7 @@ impl TextureBlock {
         self.y
     }
     pub fn width(&self) -> u32 {
-        self.width
+        self.width_and_mips.value_24()
     }
     pub fn height(&self) -> u32 {
         self.height_and_layer.value_24()
@@ -49,14 +62,27 @@ impl TextureBlock {
     pub fn layer(&self) -> u8 {
         self.height_and_layer.value_8()
     }
+    /// Number of mip levels usable by the block.
+    ///
+    /// Small blocks use fewer levels than the atlas, to avoid sampling
+    /// levels made mostly of gutter.
+    pub fn mip_level_count(&self) -> u8 {
+        self.width_and_mips.value_8()
+    }
 }
 
 unsafe impl bytemuck::Pod for TextureBlock {}
 unsafe impl bytemuck::Zeroable for TextureBlock {}
 
+/// Allocates texture blocks in the layers of an atlas.
+///
+/// Blocks are surrounded by a gutter, and aligned such that each mip level
+/// of a block only covers texels of that block. Filtering a block at any
+/// level thus doesn't bleed onto its neighbours.
 pub struct Atlas2D {
     atlas: Vec<AtlasAllocator>,
     blocks: Vec<TextureBlock>,
+    mip_level_count: u32,
 }
 
 pub struct TextureId(u32);
@@ -68,34 +94,63 @@ impl TextureId {
 }
 
 impl Atlas2D {
-    fn create_atlas_allocator(size: u32) -> AtlasAllocator {
-        AtlasAllocator::new(size2(size as i32, size as i32))
+    fn create_atlas_allocator(size: u32, alignment: u32) -> AtlasAllocator {
+        AtlasAllocator::with_options(
+            size2(size as i32, size as i32),
+            &AllocatorOptions {
+                alignment: size2(alignment as i32, alignment as i32),
+                ..Default::default()
+            },
+        )
     }
 
     pub fn new(max_size: u32) -> Self {
+        Self::with_mips(max_size, 1)
+    }
+
+    /// Create an atlas with `mip_level_count` mip levels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` isn't a multiple of the size of a texel
+    /// of the last level.
+    // `u32::is_multiple_of` would raise the minimum Rust version to 1.87.
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub fn with_mips(max_size: u32, mip_level_count: u32) -> Self {
+        let mip_level_count = mip_level_count.clamp(1, max_size.max(1).ilog2() + 1);
+        let alignment = 1 << (mip_level_count - 1);
+        assert!(
+            max_size % alignment == 0,
+            "Atlas size {} isn't a multiple of {}",
+            max_size,
+            alignment
+        );
         Self {
-            atlas: vec![Self::create_atlas_allocator(max_size)],
+            atlas: vec![Self::create_atlas_allocator(max_size, alignment)],
             blocks: vec![],
+            mip_level_count,
         }
     }
 
     pub fn reserve(&mut self, width: u32, height: u32) -> TextureId {
-        let tex_size = size2(width as i32, height as i32);
+        let gutter = 2 * self.gutter();
+        let tex_size = size2((width + gutter) as i32, (height + gutter) as i32);
         for (i, atlas) in self.atlas.iter_mut().enumerate() {
             match atlas.allocate(tex_size) {
-                Some(alloc) => return self.reserve_internal(i, alloc),
+                Some(alloc) => return self.reserve_internal(i, alloc, width, height),
                 _ => (),
             }
         }
         // No atlas found, allocate a new one.
         let layer = {
-            let size = self.atlas.first().unwrap().size();
+            let size = self.size();
             let layer = self.atlas.len();
-            self.atlas.push(AtlasAllocator::new(size));
+            self.atlas
+                .push(Self::create_atlas_allocator(size, self.alignment()));
             layer
         };
         let alloc = self.atlas[layer].allocate(tex_size).unwrap();
-        self.reserve_internal(layer, alloc)
+        self.reserve_internal(layer, alloc, width, height)
     }
 
     pub fn blocks(&self) -> &[TextureBlock] {
@@ -110,18 +165,115 @@ impl Atlas2D {
         self.atlas.first().unwrap().size().width as u32
     }
 
-    fn reserve_internal(&mut self, layer: usize, alloc: Allocation) -> TextureId {
-        let x = alloc.rectangle.min.x as u32;
-        let y = alloc.rectangle.min.y as u32;
-        let width = alloc.rectangle.width() as u32;
-        let height = alloc.rectangle.height() as u32;
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    /// Width of the border around each block, in texels of the first level.
+    ///
+    /// The gutter is one texel wide in the last level, such that bilinear
+    /// filtering wraps around the block even without mips.
+    pub fn gutter(&self) -> u32 {
+        self.alignment()
+    }
+
+    /// Alignment of the padded blocks, such that they map to whole texels
+    /// in every level.
+    fn alignment(&self) -> u32 {
+        1 << (self.mip_level_count - 1)
+    }
+
+    fn reserve_internal(
+        &mut self,
+        layer: usize,
+        alloc: Allocation,
+        width: u32,
+        height: u32,
+    ) -> TextureId {
+        let gutter = self.gutter();
+        let x = alloc.rectangle.min.x as u32 + gutter;
+        let y = alloc.rectangle.min.y as u32 + gutter;
+        let mips = u32::min(self.mip_level_count, width.max(height).max(1).ilog2() + 1);
 
         let id = self.blocks.len() as u32;
-        self.blocks
-            .push(TextureBlock::new(layer as u8, x, y, width, height));
+        self.blocks.push(TextureBlock::with_mips(
+            layer as u8,
+            x,
+            y,
+            width,
+            height,
+            mips as u8,
+        ));
 
         TextureId { 0: id }
     }
+
+    /// Texels to upload for the block `id`, for each mip level.
+    ///
+    /// Returns the `(width, height, texels)` of each level, including the
+    /// gutter. Levels start at the top left corner of the gutter, i.e., at
+    /// `(block.x() - gutter, block.y() - gutter)` in the first level.
+    pub fn build_mip_chain(&self, id: &TextureId, data: &[u8]) -> Vec<(u32, u32, Vec<u8>)> {
+        let block = self.blocks[id.0 as usize];
+        build_mip_chain(
+            data,
+            block.width(),
+            block.height(),
+            self.gutter(),
+            self.alignment(),
+            self.mip_level_count,
+        )
+    }
+}
+
+/// Texels of a block surrounded by its gutter, and its mip chain.
+///
+/// The gutter repeats the block, matching the wrapping of the texture
+/// coordinates in the shaders. Levels are box filtered, directly on the
+/// stored values.
+fn build_mip_chain(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    gutter: u32,
+    alignment: u32,
+    mip_level_count: u32,
+) -> Vec<(u32, u32, Vec<u8>)> {
+    let round_up = |v: u32| v.div_ceil(alignment) * alignment;
+    let padded_width = round_up(width + 2 * gutter);
+    let padded_height = round_up(height + 2 * gutter);
+
+    let mut level = Vec::with_capacity((padded_width * padded_height * 4) as usize);
+    for y in 0..padded_height {
+        let src_y = (y + height - gutter % height) % height;
+        for x in 0..padded_width {
+            let src_x = (x + width - gutter % width) % width;
+            let offset = ((src_y * width + src_x) * 4) as usize;
+            level.extend_from_slice(&data[offset..offset + 4]);
+        }
+    }
+
+    let mut levels = vec![(padded_width, padded_height, level)];
+    for _ in 1..mip_level_count {
+        let (w, h, previous) = levels.last().unwrap();
+        let (w, h) = (*w, *h);
+        let mut next = Vec::with_capacity((w * h) as usize);
+        for y in 0..h / 2 {
+            for x in 0..w / 2 {
+                for c in 0..4 {
+                    let texel =
+                        |tx: u32, ty: u32| previous[((ty * w + tx) * 4 + c) as usize] as u32;
+                    let sum = texel(2 * x, 2 * y)
+                        + texel(2 * x + 1, 2 * y)
+                        + texel(2 * x, 2 * y + 1)
+                        + texel(2 * x + 1, 2 * y + 1);
+                    next.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        levels.push((w / 2, h / 2, next));
+    }
+    levels
 }
 
 fn rgba_bytes_per_row(width: u32) -> u32 {
@@ -150,7 +302,7 @@ impl TextureAtlas {
                 height: atlas_size,
                 depth_or_array_layers: atlas.layer_count(),
             },
-            mip_level_count: 1,
+            mip_level_count: atlas.mip_level_count(),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8Unorm,
@@ -194,6 +346,17 @@ impl TextureAtlas {
         Self::from_atlas2d(device, atlas, Some(max_texture_count))
     }
 
+    /// Create an atlas with `mip_level_count` mip levels, see [`Atlas2D::with_mips`].
+    pub fn with_mips(
+        device: &wgpu::Device,
+        size: u32,
+        max_texture_count: u32,
+        mip_level_count: u32,
+    ) -> Self {
+        let atlas = Atlas2D::with_mips(size, mip_level_count);
+        Self::from_atlas2d(device, atlas, Some(max_texture_count))
+    }
+
     pub fn from_limits(device: &wgpu::Device) -> Self {
         let limits = device.limits();
         Self::new(
@@ -203,33 +366,41 @@ impl TextureAtlas {
         )
     }
 
+    /// Upload the RGBA8 texels of a block.
+    ///
+    /// The gutter and the mip chain of the block are generated before
+    /// uploading, see [`Atlas2D::build_mip_chain`].
     pub fn upload(&self, queue: &wgpu::Queue, id: TextureId, data: &[u8]) {
         let block = self.atlas.blocks[id.0 as usize];
 
         // Write texture data.
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.texture,
-                aspect: wgpu::TextureAspect::All,
-                mip_level: 0,
-                origin: wgpu::Origin3d {
-                    x: block.x,
-                    y: block.y,
-                    z: block.layer() as u32,
+        let gutter = self.atlas.gutter();
+        let levels = self.atlas.build_mip_chain(&id, data);
+        for (level, (width, height, texels)) in levels.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    aspect: wgpu::TextureAspect::All,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d {
+                        x: (block.x - gutter) >> level,
+                        y: (block.y - gutter) >> level,
+                        z: block.layer() as u32,
+                    },
                 },
-            },
-            data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(rgba_bytes_per_row(block.width())),
-                rows_per_image: Some(block.height()),
-            },
-            wgpu::Extent3d {
-                width: block.width(),
-                height: block.height(),
-                depth_or_array_layers: 1,
-            },
-        );
+                texels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(rgba_bytes_per_row(*width)),
+                    rows_per_image: Some(*height),
+                },
+                wgpu::Extent3d {
+                    width: *width,
+                    height: *height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         // Write texture block
         let block_data = bytemuck::bytes_of(&block);
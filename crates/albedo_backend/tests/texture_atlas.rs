@@ -0,0 +1,89 @@
+use albedo_backend::gpu::{Atlas2D, TextureBlock};
+
+/// RGBA8 texels, with all channels set to the same value.
+fn texels(values: &[u8]) -> Vec<u8> {
+    values.iter().flat_map(|v| [*v; 4]).collect()
+}
+
+#[test]
+fn texture_block_mips() {
+    let block = TextureBlock::new(2, 8, 16, 32, 64);
+    assert_eq!(
+        (
+            block.layer(),
+            block.x(),
+            block.y(),
+            block.width(),
+            block.height()
+        ),
+        (2, 8, 16, 32, 64)
+    );
+    assert_eq!(block.mip_level_count(), 1);
+    assert_eq!(
+        TextureBlock::with_mips(0, 0, 0, 32, 64, 6).mip_level_count(),
+        6
+    );
+}
+
+#[test]
+fn texture_atlas_gutter() {
+    // Blocks are padded by a texel, even without mips.
+    let mut atlas = Atlas2D::new(64);
+    assert_eq!(atlas.gutter(), 1);
+    let id = atlas.reserve(2, 2);
+    let block = atlas.blocks()[0];
+    assert_eq!((block.x(), block.y()), (1, 1));
+
+    // The gutter wraps around the block.
+    let levels = atlas.build_mip_chain(&id, &texels(&[1, 2, 3, 4]));
+    assert_eq!(levels.len(), 1);
+    let (width, height, level) = &levels[0];
+    assert_eq!((*width, *height), (4, 4));
+    assert_eq!(
+        *level,
+        texels(&[
+            4, 3, 4, 3, //
+            2, 1, 2, 1, //
+            4, 3, 4, 3, //
+            2, 1, 2, 1, //
+        ])
+    );
+}
+
+#[test]
+fn texture_atlas_mip_chain() {
+    // Two levels, with a gutter of one texel in the last level.
+    let mut atlas = Atlas2D::with_mips(64, 2);
+    assert_eq!(atlas.gutter(), 2);
+    let id = atlas.reserve(2, 4);
+    let block = atlas.blocks()[0];
+    assert_eq!((block.x(), block.y()), (2, 2));
+    assert_eq!(block.mip_level_count(), 2);
+
+    let levels = atlas.build_mip_chain(&id, &texels(&[10, 20, 30, 40, 50, 60, 70, 81]));
+    let sizes: Vec<(u32, u32)> = levels.iter().map(|(w, h, _)| (*w, *h)).collect();
+    assert_eq!(sizes, [(6, 8), (3, 4)]);
+    assert_eq!(
+        levels[0].2,
+        texels(&[
+            50, 60, 50, 60, 50, 60, //
+            70, 81, 70, 81, 70, 81, //
+            10, 20, 10, 20, 10, 20, //
+            30, 40, 30, 40, 30, 40, //
+            50, 60, 50, 60, 50, 60, //
+            70, 81, 70, 81, 70, 81, //
+            10, 20, 10, 20, 10, 20, //
+            30, 40, 30, 40, 30, 40, //
+        ])
+    );
+    // Box filtered, with rounding.
+    assert_eq!(
+        levels[1].2,
+        texels(&[
+            65, 65, 65, //
+            25, 25, 25, //
+            65, 65, 65, //
+            25, 25, 25, //
+        ])
+    );
+}
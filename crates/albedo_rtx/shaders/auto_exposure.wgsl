@@ -0,0 +1,105 @@
+// Automatic exposure.
+//
+// `histogram` bins the log luminance of the accumulated image, with one
+// `16x16` workgroup per tile. `average` then runs a single workgroup, with
+// one invocation per bin, and writes the exposure mapping the average
+// luminance to middle grey.
+//
+// The histogram must be cleared before `histogram`.
+
+const BIN_COUNT: u32 = 256u;
+const MIDDLE_GREY: f32 = 0.18;
+
+struct GlobalUniforms {
+  frame: u32,
+  seed: u32,
+  bounces: u32,
+  sample_clamp: f32,
+  dimensions: vec2<u32>,
+}
+
+struct Parameters {
+  min_log_luminance: f32,
+  max_log_luminance: f32,
+  compensation: f32,
+  adaptation: f32,
+}
+
+struct Exposure {
+  scale: f32,
+  average_luminance: f32,
+  padding: vec2<u32>,
+}
+
+@group(0) @binding(0) var accumulated: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> global: GlobalUniforms;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+@group(0) @binding(3) var<storage, read_write> bins: array<atomic<u32>, BIN_COUNT>;
+@group(0) @binding(4) var<storage, read_write> exposure: Exposure;
+
+var<workgroup> local_bins: array<atomic<u32>, BIN_COUNT>;
+// Sum of the bin indices weighted by their count, and sum of the counts.
+var<workgroup> sums: array<vec2<f32>, BIN_COUNT>;
+
+// Black pixels use the first bin, ignored by the average.
+fn luminance_bin(color: vec3<f32>) -> u32 {
+  let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+  if (luminance < 1e-5) {
+    return 0u;
+  }
+  let range = parameters.max_log_luminance - parameters.min_log_luminance;
+  let t = clamp((log2(luminance) - parameters.min_log_luminance) / range, 0.0, 1.0);
+  return u32(t * f32(BIN_COUNT - 2u)) + 1u;
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn histogram(
+  @builtin(global_invocation_id) id: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  atomicStore(&local_bins[local_index], 0u);
+  workgroupBarrier();
+
+  if (all(id.xy < global.dimensions)) {
+    let texel = textureLoad(accumulated, vec2<i32>(id.xy), 0);
+    if (texel.a > 0.0) {
+      atomicAdd(&local_bins[luminance_bin(texel.rgb / texel.a)], 1u);
+    }
+  }
+
+  workgroupBarrier();
+  atomicAdd(&bins[local_index], atomicLoad(&local_bins[local_index]));
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn average(@builtin(local_invocation_index) local_index: u32) {
+  var count = f32(atomicLoad(&bins[local_index]));
+  if (local_index == 0u) {
+    count = 0.0;
+  }
+  sums[local_index] = vec2<f32>(count * f32(local_index), count);
+  workgroupBarrier();
+
+  for (var stride = BIN_COUNT / 2u; stride > 0u; stride = stride / 2u) {
+    if (local_index < stride) {
+      sums[local_index] += sums[local_index + stride];
+    }
+    workgroupBarrier();
+  }
+
+  // Fully black images keep the previous exposure.
+  if (local_index != 0u || sums[0].y <= 0.0) {
+    return;
+  }
+
+  let bin = sums[0].x / sums[0].y - 1.0;
+  let range = parameters.max_log_luminance - parameters.min_log_luminance;
+  let luminance = exp2(bin / f32(BIN_COUNT - 2u) * range + parameters.min_log_luminance);
+
+  var adapted = luminance;
+  if (exposure.average_luminance > 0.0) {
+    adapted = mix(exposure.average_luminance, luminance, parameters.adaptation);
+  }
+  exposure.average_luminance = adapted;
+  exposure.scale = exp2(parameters.compensation) * MIDDLE_GREY / adapted;
+}
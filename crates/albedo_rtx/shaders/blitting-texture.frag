@@ -3,17 +3,27 @@
 #extension GL_EXT_samplerless_texture_functions : enable
 
 #include "imports/colorspace.glsl"
+#include "imports/tonemapping.glsl"
 
 layout(location = 0) in vec2 vUv;
 
 layout(set = 0, binding = 0) uniform sampler uTextureSampler;
 layout(set = 0, binding = 1) uniform texture2D uTexture;
+layout(set = 0, binding = 2) uniform TonemappingBuffer {
+  Tonemapping tonemapping;
+};
+layout(set = 0, binding = 3, std430) readonly buffer ExposureBuffer {
+  float scale;
+  float averageLuminance;
+  uvec2 padding;
+} exposure;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
   outColor = texture(sampler2D(uTexture, uTextureSampler), vUv).rgba;
-  outColor.rgb = ACESFilmTonemapping(outColor.rgb);
+  outColor.rgb *= exposure.scale;
+  outColor.rgb = tonemap(outColor.rgb, tonemapping);
   outColor.rgb = linearTosRGB(outColor.rgb);
   outColor.a = 1.0;
 }
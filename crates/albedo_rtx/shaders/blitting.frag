@@ -5,6 +5,7 @@
 // @todo: split global uniforms.
 #include "imports/structures.glsl"
 #include "imports/colorspace.glsl"
+#include "imports/tonemapping.glsl"
 
 layout( location = 0 ) in vec2 vUv;
 
@@ -13,13 +14,29 @@ layout( set = 0, binding = 1 ) uniform texture2D uTexture;
 layout (set = 0, binding = 2) uniform GlobalUniformBuffer {
   GlobalUniforms global;
 };
+layout (set = 0, binding = 3, std430) readonly buffer ExposureBuffer {
+  float scale;
+  float averageLuminance;
+  uvec2 padding;
+} exposure;
+layout (set = 0, binding = 4) uniform TonemappingBuffer {
+  Tonemapping tonemapping;
+};
+layout( set = 0, binding = 5 ) uniform texture2D uAlpha;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
   vec2 uv = vUv * vec2(global.dimensions) / vec2(textureSize(uTexture, 0));
-  outColor = texture(sampler2D(uTexture, uTextureSampler), uv).rgba / float(global.frame);
-  outColor.rgb = ACESFilmTonemapping(outColor.rgb);
-  outColor.rgb = linearTosRGB(outColor.rgb);
-  outColor.a = 1.0;
+  vec4 accumulated = texture(sampler2D(uTexture, uTextureSampler), uv);
+  float alphaSum = texture(sampler2D(uAlpha, uTextureSampler), uv).r;
+  float alpha = accumulated.a > 0.0 ? clamp(alphaSum / accumulated.a, 0.0, 1.0) : 0.0;
+  // The radiance is premultiplied by the alpha, and is thus divided
+  // before tonemapping.
+  float weight = accumulated.a * alpha;
+  outColor.rgb = weight > 0.0 ? accumulated.rgb / weight : vec3(0.0);
+  outColor.rgb *= exposure.scale;
+  outColor.rgb = tonemap(outColor.rgb, tonemapping);
+  outColor.rgb = linearTosRGB(outColor.rgb) * alpha;
+  outColor.a = alpha;
 }
@@ -31,4 +31,14 @@ float luminance(vec3 color) {
   return dot(color, vec3(0.2126, 0.7152, 0.0722));
 }
 
+/**
+ * Scales `color` down to a luminance of at most `maxLuminance`, keeping
+ * its hue. A maximum of `0` leaves the color unchanged.
+ */
+vec3 clampLuminance(vec3 color, float maxLuminance) {
+  float lum = luminance(color);
+  if (maxLuminance <= 0.0 || lum <= maxLuminance) return color;
+  return color * (maxLuminance / lum);
+}
+
 #endif // COLORSPACE_H
@@ -0,0 +1,33 @@
+#ifndef COMPACTION_H
+#define COMPACTION_H
+
+/**
+ * Ray compaction, only compute shaders can include this file.
+ *
+ * Passes running after the compaction are dispatched indirectly, with one
+ * invocation per active ray.
+ */
+
+/**
+ * Header of the active ray list, followed by the ray indices.
+ *
+ * Workgroups are laid out as indirect dispatch arguments.
+ */
+struct ActiveRays {
+  uint workgroupsX;
+  uint workgroupsY;
+  uint workgroupsZ;
+  uint count;
+};
+
+/**
+ * Index of the invocation in an indirect dispatch over active rays.
+ */
+uint
+activeRaySlot()
+{
+  uint workgroup = gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x;
+  return workgroup * gl_WorkGroupSize.x * gl_WorkGroupSize.y + gl_LocalInvocationIndex;
+}
+
+#endif // COMPACTION_H
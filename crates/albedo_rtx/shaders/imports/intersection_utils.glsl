@@ -112,12 +112,14 @@ as_uchar4(float val)
  * - __bind -> findMSB
  * - popc -> bitCount
  * - (u)char -> bitfieldExtract
+ * - Optional backface culling
  */
 #ifndef DEBUG_CWBVH_TRAVERSAL
 vec4
-traverse_cwbvh(Ray ray, uint bvhNodeStart, uint primitiveStart, float t)
+traverse_cwbvh(Ray ray, uint bvhNodeStart, uint primitiveStart, float t, bool cullBackfaces)
 #else
-traverse_cwbvh(Ray ray, uint bvhNodeStart, uint primitiveStart, float t, inout uint stepCount)
+vec4
+traverse_cwbvh(Ray ray, uint bvhNodeStart, uint primitiveStart, float t, bool cullBackfaces, inout uint stepCount)
 #endif
 {
 	const vec4 O4 = vec4(ray.origin, 1.0);
@@ -277,6 +279,9 @@ traverse_cwbvh(Ray ray, uint bvhNodeStart, uint primitiveStart, float t, inout u
 			vec3 r = cross( D4.xyz, e1 );
 			float a = dot( e2, r );
 			if (abs( a ) < EPSILON) continue;
+			// Edges are stored as `v2 - v0` and `v1 - v0`, the determinant is
+			// thus negative for back faces.
+			if (cullBackfaces && a < 0.0) continue;
 			float f = 1.0 / a;
 			vec3 s = O4.xyz - v0.xyz;
 			float u = f * dot( s, r );
@@ -319,6 +324,7 @@ sceneHit(Ray ray)
   intersection.dist = MAX_FLOAT;
   intersection.index = INVALID_UINT;
   intersection.instance = INVALID_UINT;
+  intersection.materialIndex = INVALID_UINT;
   intersection.emitter = INVALID_UINT;
 
   for (uint i = 0; i < instances.length(); ++i)
@@ -328,9 +334,9 @@ sceneHit(Ray ray)
     // Performs intersection in model space.
     Ray rayModel = transformRay(ray, instance.worldToModel);
 	#ifndef DEBUG_CWBVH_TRAVERSAL
-	vec4 hit = traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, intersection.dist);
+	vec4 hit = traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, intersection.dist, instance.cullBackfaces != 0u);
 	#else
-	vec4 hit = traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, intersection.dist, stepCount);
+	vec4 hit = traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, intersection.dist, instance.cullBackfaces != 0u, stepCount);
 	#endif
 	if (hit.x < intersection.dist)
     {
@@ -353,7 +359,7 @@ uint sceneTraversal(Ray ray)
   	{
 		Instance instance = instances[i];
 		Ray rayModel = transformRay(ray, instance.worldToModel);
-		traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, MAX_FLOAT, stepCount);
+		traverse_cwbvh(rayModel, instance.bvhRootIndex, instance.primitiveRootIndex, MAX_FLOAT, instance.cullBackfaces != 0u, stepCount);
 	}
 	return stepCount;
 }
@@ -0,0 +1,81 @@
+#ifndef MEDIUM_H
+#define MEDIUM_H
+
+bool
+isVacuum(const Medium medium)
+{
+  vec3 sigmaT = medium.absorption + medium.scattering;
+  return max(sigmaT.x, max(sigmaT.y, sigmaT.z)) <= EPSILON;
+}
+
+/**
+ * Samples a collision distance in a homogeneous medium.
+ *
+ * Distances are sampled using the largest extinction coefficient as a
+ * majorant, the weight then corrects for the other channels.
+ *
+ * @param medium The medium the ray travels in
+ * @param tMax Distance to the next surface
+ * @param weight The sample weight, i.e., `transmittance * scattering / pdf`
+ *   for a collision and `transmittance / probability` otherwise
+ * @param seed The current value of a seed variable
+ *
+ * @return The collision distance, `tMax` if the surface is reached first
+ */
+float
+sampleFreeFlight(const Medium medium, float tMax, out vec3 weight, inout uint seed)
+{
+  weight = vec3(1.0);
+
+  vec3 sigmaT = medium.absorption + medium.scattering;
+  float majorant = max(sigmaT.x, max(sigmaT.y, sigmaT.z));
+  if (majorant <= EPSILON) { return tMax; }
+
+  float t = - log(max(EPSILON, 1.0 - rand(seed))) / majorant;
+  if (t >= tMax)
+  {
+    // Ratio tracking, closed-form for homogeneous media.
+    weight = exp((majorant - sigmaT) * tMax);
+    return tMax;
+  }
+  weight = exp((majorant - sigmaT) * t) * medium.scattering / majorant;
+  return t;
+}
+
+/**
+ * Samples the Henyey-Greenstein phase function.
+ *
+ * The phase function is sampled exactly, the sample weight is thus `1`.
+ *
+ * @param dir The propagation direction
+ * @param g The anisotropy, positive for forward scattering
+ * @param seed The current value of a seed variable
+ *
+ * @return The scattered propagation direction
+ */
+vec3
+sampleHenyeyGreenstein(const vec3 dir, float g, inout uint seed)
+{
+  float u = rand(seed);
+  float cosTheta;
+  if (abs(g) < 1e-3)
+  {
+    cosTheta = 1.0 - 2.0 * u;
+  }
+  else
+  {
+    float sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+    cosTheta = (1.0 + g * g - sq * sq) / (2.0 * g);
+  }
+  cosTheta = clamp(cosTheta, - 1.0, 1.0);
+  float sinTheta = sqrt(max(0.0, 1.0 - cosTheta * cosTheta));
+  float phi = TWO_PI * rand(seed);
+
+  vec3 worldUp = abs(dir.z) < 0.9999 ? vec3(0, 0, 1) : vec3(1, 0, 0);
+  vec3 tangent = normalize(cross(worldUp, dir));
+  vec3 bitangent = cross(dir, tangent);
+  vec3 local = vec3(sinTheta * cos(phi), sinTheta * sin(phi), cosTheta);
+  return normalize(project(local, dir, tangent, bitangent));
+}
+
+#endif // MEDIUM_H
@@ -0,0 +1,179 @@
+#ifndef PROCEDURAL_H
+#define PROCEDURAL_H
+
+/**
+ * Procedural textures, evaluated from the node graphs stored in the
+ * `proceduralNodes` texture.
+ *
+ * A procedural texture is referenced like an atlas texture, with:
+ * - Bit 31: `PROCEDURAL_TEXTURE_BIT`
+ * - Bits 24-30: Number of nodes
+ * - Bits 0-23: Index of the first node
+ *
+ * Each node uses two texels:
+ * - `(type, inputA, inputB, inputC)`, with inputs relative to the first node
+ * - Parameters, as float bits
+ *
+ * Nodes are sorted such that inputs come first, the last node is the output.
+ */
+
+#define PROCEDURAL_MAX_NODES 16
+
+#define NODE_CONSTANT 0u
+#define NODE_CHECKER 1u
+#define NODE_GRADIENT 2u
+#define NODE_NOISE 3u
+#define NODE_VORONOI 4u
+#define NODE_MIX 5u
+#define NODE_MULTIPLY 6u
+
+uint
+pcgHash(uint value)
+{
+  uint state = value * 747796405u + 2891336453u;
+  uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+  return (word >> 22u) ^ word;
+}
+
+uint
+hashCell(ivec2 cell)
+{
+  return pcgHash(uint(cell.x) + pcgHash(uint(cell.y)));
+}
+
+float
+hashToFloat(uint hash)
+{
+  return float(hash >> 8u) * (1.0 / 16777216.0);
+}
+
+float
+checkerPattern(vec2 p)
+{
+  ivec2 cell = ivec2(floor(p));
+  return float((cell.x + cell.y) & 1);
+}
+
+float
+gradientDot(ivec2 cell, vec2 offset)
+{
+  float angle = hashToFloat(hashCell(cell)) * TWO_PI;
+  return dot(vec2(cos(angle), sin(angle)), offset);
+}
+
+/**
+ * Perlin gradient noise, in `[-sqrt(0.5); sqrt(0.5)]`.
+ */
+float
+perlinNoise(vec2 p)
+{
+  ivec2 cell = ivec2(floor(p));
+  vec2 f = fract(p);
+  vec2 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+  float n00 = gradientDot(cell, f);
+  float n10 = gradientDot(cell + ivec2(1, 0), f - vec2(1.0, 0.0));
+  float n01 = gradientDot(cell + ivec2(0, 1), f - vec2(0.0, 1.0));
+  float n11 = gradientDot(cell + ivec2(1, 1), f - vec2(1.0, 1.0));
+  return mix(mix(n00, n10, u.x), mix(n01, n11, u.x), u.y);
+}
+
+/**
+ * Fractal sum of Perlin noise octaves, remapped to `[0; 1]`.
+ */
+float
+noisePattern(vec2 p, uint octaves)
+{
+  float sum = 0.0;
+  float amplitude = 1.0;
+  float norm = 0.0;
+  for (uint i = 0u; i < max(octaves, 1u); ++i)
+  {
+    sum += amplitude * perlinNoise(p);
+    norm += amplitude;
+    p *= 2.0;
+    amplitude *= 0.5;
+  }
+  return clamp(0.5 + 0.70710678 * sum / norm, 0.0, 1.0);
+}
+
+/**
+ * Distance to the closest feature point, one point jittered per cell.
+ */
+float
+voronoiPattern(vec2 p, float randomness)
+{
+  ivec2 cell = ivec2(floor(p));
+  vec2 f = fract(p);
+  float dist = 8.0;
+  for (int y = -1; y <= 1; ++y)
+  {
+    for (int x = -1; x <= 1; ++x)
+    {
+      ivec2 neighbor = ivec2(x, y);
+      uint hash = hashCell(cell + neighbor);
+      vec2 jitter = vec2(hashToFloat(hash), hashToFloat(pcgHash(hash))) - 0.5;
+      vec2 point = vec2(neighbor) + 0.5 + jitter * randomness;
+      dist = min(dist, length(point - f));
+    }
+  }
+  return clamp(dist, 0.0, 1.0);
+}
+
+uvec4
+fetchProceduralNode(uint texel)
+{
+  return texelFetch(usampler1D(proceduralNodes, samplerNearest), int(texel), 0);
+}
+
+vec4
+evaluateProceduralTexture(uint textureIndex, vec2 uv)
+{
+  uint first = textureIndex & 0x00FFFFFFu;
+  uint count = min((textureIndex >> 24u) & 0x7Fu, uint(PROCEDURAL_MAX_NODES));
+
+  vec4 values[PROCEDURAL_MAX_NODES];
+  vec4 value = vec4(0.0);
+  for (uint i = 0u; i < count; ++i)
+  {
+    uint texel = (first + i) * 2u;
+    uvec4 node = fetchProceduralNode(texel);
+    vec4 params = uintBitsToFloat(fetchProceduralNode(texel + 1u));
+
+    // Unconnected inputs default to black and white.
+    vec4 a = node.y < i ? values[node.y] : vec4(0.0, 0.0, 0.0, 1.0);
+    vec4 b = node.z < i ? values[node.z] : vec4(1.0);
+
+    float t = 0.0;
+    switch (node.x)
+    {
+      case NODE_CHECKER:
+        t = checkerPattern(uv * params.x);
+        break;
+      case NODE_GRADIENT:
+        t = clamp(dot(uv, params.xy) + params.z, 0.0, 1.0);
+        break;
+      case NODE_NOISE:
+        t = noisePattern(uv * params.x, uint(params.y));
+        break;
+      case NODE_VORONOI:
+        t = voronoiPattern(uv * params.x, params.y);
+        break;
+      case NODE_MIX:
+        t = node.w < i ? values[node.w].x : params.x;
+        break;
+      default:
+        break;
+    }
+
+    if (node.x == NODE_CONSTANT)
+      value = params;
+    else if (node.x == NODE_MULTIPLY)
+      value = (node.y < i ? a : vec4(1.0)) * b;
+    else
+      value = mix(a, b, t);
+    values[i] = value;
+  }
+  return value;
+}
+
+#endif // PROCEDURAL_H
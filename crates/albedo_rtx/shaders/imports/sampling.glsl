@@ -19,6 +19,13 @@ struct BSDFSample
   float pdf;
 };
 
+/**
+ * Lobes of the BSDF, used to limit the path depth per lobe.
+ */
+#define LOBE_DIFFUSE 0u
+#define LOBE_GLOSSY 1u
+#define LOBE_TRANSMISSION 2u
+
 /**
  * Contains material data after processing.
  *
@@ -32,6 +39,14 @@ struct MaterialState
   float perceptualRoughness;
   float roughness;
   float roughness2;
+  float transmission;
+  float ior;
+  vec3 sheenColor;
+  float sheenRoughness;
+  float clearcoat;
+  float clearcoatRoughness;
+  float anisotropy;
+  float subsurface;
 };
 
 /*
@@ -146,6 +161,26 @@ vec3 randomSampleSpecular_GGX(
   return 2.0 * dot(w0, H) * H - w0;
 }
 
+/**
+ * Fresnel reflectance of a smooth dielectric interface.
+ *
+ * @param cosThetaI Cosine between the incident direction and the normal
+ * @param eta Relative index of refraction, i.e., `etaI / etaT`
+ *
+ * @return The reflected ratio, `1.0` in case of total internal reflection
+ */
+float
+FresnelDielectric(float cosThetaI, float eta)
+{
+  float sin2ThetaT = eta * eta * max(0.0, 1.0 - cosThetaI * cosThetaI);
+  if (sin2ThetaT >= 1.0) { return 1.0; }
+
+  float cosThetaT = sqrt(1.0 - sin2ThetaT);
+  float rs = (eta * cosThetaI - cosThetaT) / (eta * cosThetaI + cosThetaT);
+  float rp = (cosThetaI - eta * cosThetaT) / (cosThetaI + eta * cosThetaT);
+  return 0.5 * (rs * rs + rp * rp);
+}
+
 /**
  * Approximated fresnel effect.
  */
@@ -158,92 +193,398 @@ SchlickFresnel(float u)
 }
 
 /**
- * Samples the BSDF function based on geometry and material data.
+ * Express a world space direction in the local shading frame, where `z` is
+ * the normal.
+ */
+vec3
+toLocal(const vec3 v, const vec3 normal, const vec3 tangent, const vec3 bitangent)
+{
+  return vec3(dot(v, tangent), dot(v, bitangent), dot(v, normal));
+}
+
+/**
+ * Fresnel-Schlick approximation, with a custom reflectance at normal incidence.
+ */
+vec3
+FresnelSchlick(const vec3 f0, float cosTheta)
+{
+  return f0 + (vec3(1.0) - f0) * SchlickFresnel(cosTheta);
+}
+
+/**
+ * Anisotropic GGX distribution, in the local shading frame.
+ */
+float
+D_GGXAniso(const vec3 H, float alphaX, float alphaY)
+{
+  vec3 h = vec3(H.x / alphaX, H.y / alphaY, H.z);
+  float t = dot(h, h);
+  return 1.0 / max(EPSILON, PI_F * alphaX * alphaY * t * t);
+}
+
+/**
+ * Smith `Lambda` auxiliary function of the anisotropic GGX distribution.
+ */
+float
+Lambda_GGXAniso(const vec3 V, float alphaX, float alphaY)
+{
+  float a2Tan2 = (alphaX * alphaX * V.x * V.x + alphaY * alphaY * V.y * V.y) / max(EPSILON, V.z * V.z);
+  return 0.5 * (- 1.0 + sqrt(1.0 + a2Tan2));
+}
+
+/**
+ * Generate a visible microfacet normal of the anisotropic GGX distribution.
  *
- * @param w0 Surface to eye direction vector
- * @param normal The normal to the evaluated surface
+ * @param V Surface to eye direction, in the local shading frame
+ *
+ * @return The microfacet normal, in the local shading frame
+ *
+ * This method is based on:
+ *  - Sampling the GGX Distribution of Visible Normals, Heitz 2018
+ */
+vec3
+randomVisibleMicrofacet_GGX(const vec3 V, float alphaX, float alphaY, inout uint seed)
+{
+  vec3 Vh = normalize(vec3(alphaX * V.x, alphaY * V.y, V.z));
+  float lensq = Vh.x * Vh.x + Vh.y * Vh.y;
+  vec3 T1 = lensq > 0.0 ? vec3(- Vh.y, Vh.x, 0.0) * inversesqrt(lensq) : vec3(1.0, 0.0, 0.0);
+  vec3 T2 = cross(Vh, T1);
+
+  float r = sqrt(rand(seed));
+  float phi = TWO_PI * rand(seed);
+  float t1 = r * cos(phi);
+  float t2 = r * sin(phi);
+  float s = 0.5 * (1.0 + Vh.z);
+  t2 = (1.0 - s) * sqrt(max(0.0, 1.0 - t1 * t1)) + s * t2;
+
+  vec3 Nh = t1 * T1 + t2 * T2 + sqrt(max(0.0, 1.0 - t1 * t1 - t2 * t2)) * Vh;
+  return normalize(vec3(alphaX * Nh.x, alphaY * Nh.y, max(EPSILON, Nh.z)));
+}
+
+/**
+ * Generate a cosine weighted direction around the local normal.
+ */
+vec3
+sampleDiffuse_Lambert(inout uint seed)
+{
+  return randomCosineWeightedVector(seed);
+}
+
+/**
+ * Lambert diffuse lobe, multiplied by the cosine term.
+ *
+ * **Note**: The albedo is applied by the caller.
+ */
+float
+evalDiffuse_Lambert(const vec3 L)
+{
+  return max(0.0, L.z) / PI_F;
+}
+
+float
+pdfDiffuse_Lambert(const vec3 L)
+{
+  return max(0.0, L.z) / PI_F;
+}
+
+/**
+ * Sample the anisotropic GGX specular lobe using its visible normals.
+ *
+ * All directions are in the local shading frame.
+ */
+vec3
+sampleSpecular_GGX(const vec3 V, float alphaX, float alphaY, inout uint seed)
+{
+  vec3 H = randomVisibleMicrofacet_GGX(V, alphaX, alphaY, seed);
+  return reflect(- V, H);
+}
+
+/**
+ * Anisotropic GGX specular lobe, multiplied by the cosine term.
+ *
+ * Uses the height-correlated Smith masking-shadowing function.
+ *
+ * **Note**: The Fresnel term is applied by the caller.
+ */
+float
+evalSpecular_GGX(const vec3 V, const vec3 L, float alphaX, float alphaY)
+{
+  if (V.z <= EPSILON || L.z <= EPSILON) { return 0.0; }
+  vec3 H = normalize(V + L);
+  float G2 = 1.0 / (1.0 + Lambda_GGXAniso(V, alphaX, alphaY) + Lambda_GGXAniso(L, alphaX, alphaY));
+  return D_GGXAniso(H, alphaX, alphaY) * G2 / (4.0 * V.z);
+}
+
+float
+pdfSpecular_GGX(const vec3 V, const vec3 L, float alphaX, float alphaY)
+{
+  if (V.z <= EPSILON || L.z <= EPSILON) { return 0.0; }
+  vec3 H = normalize(V + L);
+  float G1 = 1.0 / (1.0 + Lambda_GGXAniso(V, alphaX, alphaY));
+  return G1 * D_GGXAniso(H, alphaX, alphaY) / (4.0 * V.z);
+}
+
+/**
+ * "Charlie" sheen distribution.
+ *
+ * This method is based on:
+ *  - Production Friendly Microfacet Sheen BRDF, Estevez and Kulla 2017
+ */
+float
+D_Charlie(float NdotH, float roughness)
+{
+  float invR = 1.0 / roughness;
+  float sin2 = max(0.0, 1.0 - NdotH * NdotH);
+  return (2.0 + invR) * pow(sin2, 0.5 * invR) / TWO_PI;
+}
+
+float
+L_Charlie(float x, float roughness)
+{
+  float t = (1.0 - roughness) * (1.0 - roughness);
+  float a = mix(21.5473, 25.3245, t);
+  float b = mix(3.82987, 3.32435, t);
+  float c = mix(0.19823, 0.16801, t);
+  float d = mix(- 1.97760, - 1.27393, t);
+  float e = mix(- 4.32054, - 4.85967, t);
+  return a / (1.0 + b * pow(x, c)) + d * x + e;
+}
+
+float
+Lambda_Charlie(float cosTheta, float roughness)
+{
+  return cosTheta < 0.5
+    ? exp(L_Charlie(cosTheta, roughness))
+    : exp(2.0 * L_Charlie(0.5, roughness) - L_Charlie(1.0 - cosTheta, roughness));
+}
+
+vec3
+sampleSheen_Charlie(inout uint seed)
+{
+  return randomCosineWeightedVector(seed);
+}
+
+/**
+ * Sheen lobe, multiplied by the cosine term.
+ *
+ * **Note**: The sheen color is applied by the caller.
+ */
+float
+evalSheen_Charlie(const vec3 V, const vec3 L, float roughness)
+{
+  if (V.z <= EPSILON || L.z <= EPSILON) { return 0.0; }
+  vec3 H = normalize(V + L);
+  float G2 = 1.0 / (1.0 + Lambda_Charlie(V.z, roughness) + Lambda_Charlie(L.z, roughness));
+  return D_Charlie(H.z, roughness) * G2 / (4.0 * V.z);
+}
+
+float
+pdfSheen_Charlie(const vec3 L)
+{
+  return pdfDiffuse_Lambert(L);
+}
+
+/**
+ * Directional albedo of the white "Charlie" sheen lobe, integrated offline.
+ *
+ * Rows go from the smoothest to the roughest sheen, indexed by
+ * `sqrt((roughness - 0.07) / 0.93)`. Columns are indexed by `sqrt(cosTheta)`.
+ */
+const float SHEEN_ALBEDO[128] = float[128](
+  0.9733, 0.9901, 0.9167, 0.7836, 0.6440, 0.5137, 0.3977, 0.2975, 0.2136, 0.1456, 0.0929, 0.0543, 0.0283, 0.0122, 0.0036, 0.0003,
+  0.9065, 0.9252, 0.8665, 0.7513, 0.6285, 0.5122, 0.4070, 0.3143, 0.2344, 0.1674, 0.1131, 0.0711, 0.0407, 0.0200, 0.0073, 0.0009,
+  0.8122, 0.8340, 0.7975, 0.7091, 0.6117, 0.5173, 0.4298, 0.3501, 0.2784, 0.2148, 0.1593, 0.1124, 0.0748, 0.0452, 0.0225, 0.0064,
+  0.7766, 0.8017, 0.7795, 0.7069, 0.6244, 0.5433, 0.4670, 0.3961, 0.3304, 0.2699, 0.2145, 0.1646, 0.1219, 0.0850, 0.0528, 0.0249,
+  0.7978, 0.8262, 0.8116, 0.7448, 0.6674, 0.5911, 0.5190, 0.4514, 0.3880, 0.3283, 0.2722, 0.2200, 0.1740, 0.1325, 0.0938, 0.0574,
+  0.8519, 0.8830, 0.8709, 0.8038, 0.7258, 0.6491, 0.5768, 0.5090, 0.4451, 0.3846, 0.3270, 0.2726, 0.2243, 0.1798, 0.1377, 0.0971,
+  0.9032, 0.9355, 0.9231, 0.8538, 0.7738, 0.6957, 0.6224, 0.5539, 0.4895, 0.4282, 0.3698, 0.3145, 0.2652, 0.2198, 0.1765, 0.1349,
+  0.9017, 0.9337, 0.9220, 0.8546, 0.7771, 0.7016, 0.6312, 0.5655, 0.5038, 0.4452, 0.3891, 0.3359, 0.2886, 0.2451, 0.2036, 0.1638
+);
+
+/**
+ * Ratio of energy reflected by the white sheen lobe, used to scale the
+ * layers underneath.
+ *
+ * @param cosTheta Cosine between the view direction and the normal
+ * @param roughness Sheen roughness, in `[0.07; 1]`
+ */
+float
+albedoSheen_Charlie(float cosTheta, float roughness)
+{
+  vec2 uv = sqrt(clamp(vec2(cosTheta, (roughness - 0.07) / 0.93), 0.0, 1.0)) * vec2(15.0, 7.0);
+  ivec2 i = min(ivec2(uv), ivec2(14, 6));
+  vec2 f = uv - vec2(i);
+  int row = i.y * 16 + i.x;
+  float e0 = mix(SHEEN_ALBEDO[row], SHEEN_ALBEDO[row + 1], f.x);
+  float e1 = mix(SHEEN_ALBEDO[row + 16], SHEEN_ALBEDO[row + 17], f.x);
+  return mix(e0, e1, f.y);
+}
+
+/**
+ * Samples a rough dielectric BSDF, with both reflection and refraction.
+ *
+ * Reflection and refraction are chosen stochastically based on the Fresnel
+ * term, which then cancels out of the sample weight.
+ *
+ * @param V Surface to eye direction vector, in the local shading frame
  * @param mat The material data
+ * @param eta Relative index of refraction, i.e., `etaI / etaT`
+ * @param weight The sample weight, i.e., `bsdf * cos / pdf`
+ * @param lobe The sampled lobe, glossy or transmission
  * @param seed The current value of a seed variable
+ *
+ * @return The sampled direction, in the local shading frame
+ *
+ * This method is based on:
+ *  - Microfacet Models for Refraction through Rough Surfaces, Walter et al. 2007
  */
-BSDFSample
-sampleBSDF_UE4(
-  const vec3 w0,
-  const vec3 normal,
+vec3 sampleBSDF_Dielectric(
+  const vec3 V,
   const MaterialState mat,
+  const float eta,
+  out vec3 weight,
+  out uint lobe,
   inout uint seed
 )
 {
-  BSDFSample bsdf;
+  float alpha = mat.roughness;
+  vec3 H = randomVisibleMicrofacet_GGX(V, alpha, alpha, seed);
+  float VdotH = dot(V, H);
+  float F = FresnelDielectric(abs(VdotH), eta);
 
-  vec3 worldUp = abs(normal.z) < 0.9999 ? vec3(0, 0, 1) : vec3(1, 0, 0);
-  vec3 tangent = normalize(cross(worldUp, normal));
-  vec3 bitangent = cross(normal, tangent);
+  bool reflected = rand(seed) < F;
+  vec3 L = reflected ? reflect(- V, H) : refract(- V, H, eta);
+  lobe = reflected ? LOBE_GLOSSY : LOBE_TRANSMISSION;
 
-  float diffuseRatio = 0.5 * (1.0 - mat.metallic);
-  float specularRatio = 1.0 - diffuseRatio;
-
-  /*
-   * 1. Sample BSDF direction
-   */
-
-  float probability = rand(seed);
-  if (probability < diffuseRatio)
+  // Reflection must stay in the upper hemisphere, and refraction must cross
+  // the surface.
+  if (V.z <= EPSILON || dot(L, L) < EPSILON || (reflected != (L.z > 0.0)))
   {
-    bsdf.dir = randomSampleDiffuse_Lambert(normal, tangent, bitangent, seed);
+    weight = vec3(0.0);
+    return L;
   }
-  else
+
+  // Visible normals sampling leaves only the shadowing term.
+  vec3 Lm = vec3(L.xy, abs(L.z));
+  weight = vec3(1.0 / (1.0 + Lambda_GGXAniso(Lm, alpha, alpha)));
+  if (!reflected)
   {
-    bsdf.dir = randomSampleSpecular_GGX(w0, normal, tangent, bitangent, mat.roughness2, seed);
+    // Radiance is compressed when entering a denser medium.
+    weight *= mat.albedo * eta * eta;
   }
-
-  /*
-   * 1. Sample PDF
-   */
-
-  vec3 L = bsdf.dir;
-  bsdf.H = normalize(L + w0);
-  bsdf.NdotL = dot(normal, L);
-  bsdf.NdotH = dot(normal, bsdf.H);
-	bsdf.LdotH = dot(L, bsdf.H);
-	bsdf.NdotV = dot(normal, w0);
-
-  float cosTheta = abs(bsdf.NdotH);
-  float pdfGTR2 = GTR2(cosTheta, mat.roughness2) * cosTheta;
-
-  // Calculate diffuse and specular pdfs and mix ratio
-  float pdfSpec = pdfGTR2 / (4.0 * abs(bsdf.LdotH) + EPSILON);
-  float pdfDiff = abs(bsdf.NdotL) * (1.0 / PI_F);
-
-  // Weight pdfs according to ratios
-  bsdf.pdf = diffuseRatio * pdfDiff + specularRatio * pdfSpec;
-  return bsdf;
+  return L;
 }
 
 /**
- * Evaluates a sample with the given BSDF and geometric data.
- * This method is based on a general Cook-Torrance model.
+ * Samples the layered principled BSDF, modeled after the glTF
+ * `KHR_materials_clearcoat`, `KHR_materials_sheen`, and
+ * `KHR_materials_anisotropy` extensions.
  *
- * @param bsdf The BSDF sample to evaluate
- * @param normal The normal to the evaluated surface
- * @param mat The material data
+ * Layers are stacked from top to bottom: clearcoat, sheen, transmission, and
+ * the specular / diffuse base. Each layer is picked stochastically, using its
+ * albedo as the probability, i.e., the Fresnel reflectance at the macro normal
+ * for the clearcoat, and `max(sheenColor) * E(cosTheta)` for the sheen. The
+ * layers underneath are scaled by the energy that isn't reflected, which
+ * cancels out with the probability of picking them.
+ *
+ * Subsurface scattering replaces part of the diffuse base by a diffuse
+ * transmission, i.e., a `LOBE_DIFFUSE` direction below the surface. The
+ * caller is then responsible for the random walk inside the mesh.
  *
- * This method accepts only PBR materials based on the metal-roughness
- * workflow.
+ * @param w0 Surface to eye direction vector
+ * @param normal The normal to the evaluated surface, on the side of `w0`
+ * @param tangent The anisotropy direction
+ * @param bitangent The bitangent to the evaluated surface
+ * @param mat The material data
+ * @param eta Relative index of refraction, i.e., `etaI / etaT`
+ * @param weight The sample weight, i.e., `bsdf * cos / pdf`
+ * @param lobe The sampled lobe, i.e., `LOBE_DIFFUSE`, `LOBE_GLOSSY`, or
+ *   `LOBE_TRANSMISSION`
+ * @param seed The current value of a seed variable
  *
- * This method is inspired and modified from:
- *  - OpenGLPathtracer: https://github.com/RobertBeckebans/OpenGL-PathTracer/blob/master/PathTracer/src/shaders/Progressive/PathTraceFrag.glsl
- *  - Real shading in Unreal Engine 4: https://blog.selfshadow.com/publications/s2013-shading-course/karis/s2013_pbs_epic_notes_v2.pdf
+ * @return The sampled direction
  */
-vec3 evalSample_UE4(const BSDFSample bsdf, const vec3 normal, const MaterialState mat)
+vec3
+sampleBSDF_Principled(
+  const vec3 w0,
+  const vec3 normal,
+  const vec3 tangent,
+  const vec3 bitangent,
+  const MaterialState mat,
+  const float eta,
+  out vec3 weight,
+  out uint lobe,
+  inout uint seed
+)
 {
-	if (bsdf.NdotL <= EPSILON || bsdf.NdotV <= EPSILON) { return vec3(0.0); }
+  vec3 V = toLocal(w0, normal, tangent, bitangent);
+  vec3 L = vec3(0.0, 0.0, 1.0);
+  weight = vec3(0.0);
+  lobe = LOBE_GLOSSY;
+  if (V.z <= EPSILON) { return normal; }
+
+  float coatProbability = mat.clearcoat * FresnelSchlick(vec3(0.04), V.z).x;
+  float sheenMax = max(mat.sheenColor.r, max(mat.sheenColor.g, mat.sheenColor.b));
+  float sheenProbability = sheenMax * albedoSheen_Charlie(V.z, mat.sheenRoughness);
+
+  if (rand(seed) < coatProbability)
+  {
+    float alpha = mat.clearcoatRoughness;
+    L = sampleSpecular_GGX(V, alpha, alpha, seed);
+    float pdf = pdfSpecular_GGX(V, L, alpha, alpha);
+    if (pdf > EPSILON) {
+      weight = vec3(evalSpecular_GGX(V, L, alpha, alpha) / pdf);
+    }
+  }
+  else if (rand(seed) < sheenProbability)
+  {
+    lobe = LOBE_DIFFUSE;
+    L = sampleSheen_Charlie(seed);
+    float pdf = pdfSheen_Charlie(L);
+    if (pdf > EPSILON) {
+      weight = (mat.sheenColor / sheenProbability) * evalSheen_Charlie(V, L, mat.sheenRoughness) / pdf;
+    }
+  }
+  else if (rand(seed) < mat.transmission)
+  {
+    L = sampleBSDF_Dielectric(V, mat, eta, weight, lobe, seed);
+  }
+  else
+  {
+    // glTF anisotropy stretches the roughness along the tangent.
+    float alphaX = mix(mat.roughness, 1.0, mat.anisotropy * mat.anisotropy);
+    float alphaY = mat.roughness;
+
+    vec3 specular = FresnelSchlick(mat.f0, V.z);
+    float specularProbability = max(specular.r, max(specular.g, specular.b));
+    if (rand(seed) < specularProbability)
+    {
+      L = sampleSpecular_GGX(V, alphaX, alphaY, seed);
+      float pdf = pdfSpecular_GGX(V, L, alphaX, alphaY);
+      if (pdf > EPSILON) {
+        weight = (specular / specularProbability) * evalSpecular_GGX(V, L, alphaX, alphaY) / pdf;
+      }
+    }
+    else
+    {
+      lobe = LOBE_DIFFUSE;
+      L = sampleDiffuse_Lambert(seed);
+      float pdf = pdfDiffuse_Lambert(L);
+      if (rand(seed) < mat.subsurface)
+      {
+        // Diffuse transmission, the color is given by the random walk
+        // below the surface.
+        L.z = - L.z;
+        weight = vec3(1.0 - mat.metallic);
+      }
+      else if (pdf > EPSILON)
+      {
+        weight = mat.albedo * (1.0 - mat.metallic) * evalDiffuse_Lambert(L) / pdf;
+      }
+    }
+  }
 
-	float Ds = GTR2(bsdf.NdotH, mat.roughness2);
-	float FH = SchlickFresnel(bsdf.LdotH);
-	float Gs = GeometrySmith_GGX(bsdf.NdotL, mat.roughness2) * GeometrySmith_GGX(bsdf.NdotV, mat.roughness2);
-	vec3 Fs = mix(mat.f0, vec3(1.0), FH);
-  vec3 diffuse = (mat.albedo / PI_F) * (1.0 - mat.metallic);
-	return diffuse + Gs * Fs * Ds;
+  return normalize(project(L, normal, tangent, bitangent));
 }
 
 #endif // SAMPLING_H
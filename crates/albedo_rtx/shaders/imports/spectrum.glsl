@@ -0,0 +1,224 @@
+#ifndef SPECTRUM_H
+#define SPECTRUM_H
+
+/**
+ * Spectral rendering, with 3 hero wavelengths per path.
+ *
+ * Spectral paths carry one radiance and throughput value per wavelength,
+ * stored in place of the RGB channels. The hero wavelength is packed in the
+ * upper 16 bits of `terminated.y`, and `SPECTRAL_BIT` is set in
+ * `terminated.w` once it's sampled.
+ *
+ * RGB colors use the linear sRGB primaries with an equal-energy white point,
+ * matching `spectrum.rs`.
+ *
+ * This method is based on:
+ *  - Hero Wavelength Spectral Sampling, Wilkie et al. 2014
+ */
+
+#define LAMBDA_MIN 380.0
+#define LAMBDA_MAX 780.0
+#define SPECTRUM_SAMPLES 81
+
+#define SPECTRAL_BIT 0x40000000u
+#define SECONDARY_TERMINATED_BIT 0x20000000u
+
+/**
+ * Wavelengths of a path, equally spaced from the hero one.
+ */
+vec3
+heroWavelengths(float hero)
+{
+  float range = LAMBDA_MAX - LAMBDA_MIN;
+  vec3 offsets = fract((hero - LAMBDA_MIN) / range + vec3(0.0, 1.0, 2.0) / 3.0);
+  return LAMBDA_MIN + offsets * range;
+}
+
+/**
+ * Start a spectral path, with a hero wavelength sampled uniformly.
+ */
+void
+sampleWavelengths(inout RayPayload ray, float u)
+{
+  uint packed = min(uint(u * 65536.0), 0xFFFFu);
+  ray.terminated.y = (ray.terminated.y & BOUNCE_MASK) | (packed << 16u);
+  ray.terminated.w |= SPECTRAL_BIT;
+}
+
+vec3
+rayWavelengths(RayPayload ray)
+{
+  float u = (float(ray.terminated.y >> 16u) + 0.5) / 65536.0;
+  return heroWavelengths(LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN));
+}
+
+float
+gaussianLobe(float x, float mu, float sigmaLow, float sigmaHigh)
+{
+  float t = (x - mu) / (x < mu ? sigmaLow : sigmaHigh);
+  return exp(- 0.5 * t * t);
+}
+
+/**
+ * CIE 1931 color matching functions, normalized to integrate to `1` over
+ * the visible wavelengths.
+ *
+ * This method is based on:
+ *  - Simple Analytic Approximations to the CIE XYZ Color Matching Functions, Wyman et al. 2013
+ */
+vec3
+cieXYZ(float lambda)
+{
+  float x = 1.056 * gaussianLobe(lambda, 599.8, 37.9, 31.0)
+    + 0.362 * gaussianLobe(lambda, 442.0, 16.0, 26.7)
+    - 0.065 * gaussianLobe(lambda, 501.1, 20.4, 26.2);
+  float y = 0.821 * gaussianLobe(lambda, 568.8, 46.9, 40.5)
+    + 0.286 * gaussianLobe(lambda, 530.9, 16.3, 31.1);
+  float z = 1.217 * gaussianLobe(lambda, 437.0, 11.8, 36.0)
+    + 0.681 * gaussianLobe(lambda, 459.0, 26.0, 13.8);
+  return vec3(x, y, z) / vec3(106.765046, 106.919735, 106.825325);
+}
+
+vec3
+xyzToRGB(vec3 xyz)
+{
+  // sRGB primaries, with a Bradford adaptation from the equal-energy
+  // white point to D65. Matrices are column major.
+  const mat3 XYZ_TO_RGB = mat3(
+    3.1462510, - 0.9955350, 0.0635978,
+    - 1.6661239, 1.9557634, - 0.2145965,
+    - 0.4801271, 0.0397715, 1.1509987
+  );
+  return XYZ_TO_RGB * xyz;
+}
+
+/**
+ * Monte Carlo estimate of the RGB color of a spectrum sampled at `lambdas`,
+ * drawn uniformly.
+ */
+vec3
+spectralToRGB(vec3 values, vec3 lambdas)
+{
+  vec3 xyz = cieXYZ(lambdas.x) * values.x + cieXYZ(lambdas.y) * values.y + cieXYZ(lambdas.z) * values.z;
+  return xyzToRGB(xyz * ((LAMBDA_MAX - LAMBDA_MIN) / 3.0));
+}
+
+/**
+ * Radiance of a path in RGB, converting spectral paths.
+ */
+vec3
+rayRadiance(RayPayload ray)
+{
+  if ((ray.terminated.w & SPECTRAL_BIT) == 0u) return ray.radiance.rgb;
+  return spectralToRGB(ray.radiance.rgb, rayWavelengths(ray));
+}
+
+float
+sigmoid(float x)
+{
+  return 0.5 + x / (2.0 * sqrt(1.0 + x * x));
+}
+
+vec3
+evalSigmoidPolynomial(vec3 c, vec3 lambdas)
+{
+  vec3 t = (lambdas - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+  vec3 x = (c.x * t + c.y) * t + c.z;
+  return vec3(sigmoid(x.x), sigmoid(x.y), sigmoid(x.z));
+}
+
+/**
+ * Index of refraction varying with the wavelength, following
+ * `KHR_materials_dispersion`.
+ *
+ * @param dispersion `20 / V`, with `V` the Abbe number
+ */
+float
+dispersedIOR(float ior, float dispersion, float lambda)
+{
+  float abbe = 20.0 / dispersion;
+  return max(1.0, ior + (ior - 1.0) / abbe * (523655.0 / (lambda * lambda) - 1.5168));
+}
+
+#ifdef SPECTRAL
+
+float
+inverseSmoothstep(float y)
+{
+  return 0.5 - sin(asin(1.0 - 2.0 * y) / 3.0);
+}
+
+/**
+ * Sigmoid polynomial of an RGB reflectance, interpolated from the table
+ * built by `RgbToSpectrumTable`.
+ */
+vec3
+rgbToSpectrumCoefficients(vec3 rgb)
+{
+  int res = textureSize(rgbToSpectrum, 0).x;
+  int l = rgb.r >= rgb.g && rgb.r >= rgb.b ? 0 : (rgb.g >= rgb.b ? 1 : 2);
+  float z = rgb[l];
+  vec3 coords = vec3(
+    rgb[(l + 1) % 3] / z,
+    rgb[(l + 2) % 3] / z,
+    inverseSmoothstep(inverseSmoothstep(z))
+  ) * float(res - 1);
+  ivec3 base = min(ivec3(coords), ivec3(res - 2));
+  vec3 t = coords - vec3(base);
+  base.z += l * res;
+
+  vec3 c[8];
+  for (int i = 0; i < 8; ++i)
+  {
+    ivec3 offset = ivec3(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+    c[i] = texelFetch(sampler3D(rgbToSpectrum, samplerNearest), base + offset, 0).rgb;
+  }
+  vec3 c0 = mix(mix(c[0], c[1], t.x), mix(c[2], c[3], t.x), t.y);
+  vec3 c1 = mix(mix(c[4], c[5], t.x), mix(c[6], c[7], t.x), t.y);
+  return mix(c0, c1, t.z);
+}
+
+/**
+ * Spectrum of an RGB reflectance in `[0; 1]`, evaluated at `lambdas`.
+ */
+vec3
+upsampleReflectance(vec3 rgb, vec3 lambdas)
+{
+  rgb = clamp(rgb, vec3(0.0), vec3(1.0));
+  // Uniform colors map exactly to constant spectra.
+  if (rgb.r == rgb.g && rgb.g == rgb.b) return rgb;
+  return evalSigmoidPolynomial(rgbToSpectrumCoefficients(rgb), lambdas);
+}
+
+/**
+ * Spectrum of an unbounded RGB value, e.g., emission or medium coefficients.
+ */
+vec3
+upsampleUnbounded(vec3 rgb, vec3 lambdas)
+{
+  float scale = 2.0 * max(rgb.r, max(rgb.g, rgb.b));
+  if (scale <= 0.0) return vec3(0.0);
+  return scale * upsampleReflectance(rgb / scale, lambdas);
+}
+
+/**
+ * Measured spectrum stored in the row `index` of the spectra texture.
+ */
+vec3
+evalSpectrum(uint index, vec3 lambdas)
+{
+  vec3 x = (lambdas - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * float(SPECTRUM_SAMPLES - 1);
+  vec3 result;
+  for (int i = 0; i < 3; ++i)
+  {
+    int i0 = min(int(x[i]), SPECTRUM_SAMPLES - 2);
+    float v0 = texelFetch(sampler2D(spectra, samplerNearest), ivec2(i0, index), 0).r;
+    float v1 = texelFetch(sampler2D(spectra, samplerNearest), ivec2(i0 + 1, index), 0).r;
+    result[i] = mix(v0, v1, x[i] - float(i0));
+  }
+  return result;
+}
+
+#endif // SPECTRAL
+
+#endif // SPECTRUM_H
@@ -6,7 +6,7 @@ struct GlobalUniforms
   uint frame;
   uint seed;
   uint bounces;
-  uint padding;
+  float sampleClamp;
   uvec2 dimensions;
 };
 
@@ -18,6 +18,17 @@ struct BVHNode {
   vec4 n4;
 };
 
+/**
+ * Homogeneous participating medium, see `imports/medium.glsl`.
+ */
+struct Medium
+{
+  vec3 absorption;
+  float anisotropy;
+  vec3 scattering;
+  float padding;
+};
+
 struct Instance
 {
   // @todo: reduce size of this struct.
@@ -27,12 +38,24 @@ struct Instance
   uint bvhRootIndex;
   uint vertexRootIndex;
   uint primitiveRootIndex;
+  // Medium enclosed by the mesh.
+  Medium medium;
+  // Back faces are skipped by the traversal when non-zero.
+  uint cullBackfaces;
+  uint padding_1;
+  uint padding_2;
+  uint padding_3;
 };
 
+/**
+ * - `position.w` contains the first texture coordinate
+ * - `attributes` packs the octahedral normal, octahedral tangent,
+ *   second texture coordinate, and tangent handedness
+ */
 struct Vertex
 {
   vec4 position;
-  vec4 normal;
+  uvec4 attributes;
 };
 
 struct Light
@@ -41,19 +64,43 @@ struct Light
   vec4 tangent;
   vec4 bitangent;
   float intensity;
-  float padding_0;
+  uint spectrum;
   float padding_1;
   float padding_2;
 };
 
 /**
  * - `throughput` saved in `origin.w`, `dir.w`, `radiance,w`
+ * - `terminated.x` is set once the path is terminated
+ * - `terminated.y` contains the bounce count in its lower 16 bits, see
+ *   `BOUNCE_MASK`. The upper bits store the hero wavelength of spectral paths
+ * - `terminated.z` contains the instance whose medium the ray travels in,
+ *   `INVALID_UINT` for the global medium
+ * - `terminated.w` packs the diffuse, glossy, and transmission bounce counts,
+ *   using 8 bits each. Bits 24 and 25 store the lobe sampled at the first
+ *   surface hit plus one, `0` if none, see `FIRST_LOBE_SHIFT`. Bit 26 is
+ *   set while the path leaves a shadow catcher, see `SHADOW_CATCHER_BIT`.
+ *   Bits 29 and 30 are used by spectral paths, see `imports/spectrum.glsl`.
+ *   The last bit is set if the pixel filter is negative for this sample,
+ *   see `FILTER_NEGATIVE_BIT`
+ * - `cone` contains the width of the ray cone at the origin, and its
+ *   spread angle, used to select texture levels of detail
+ * - `alpha` is the coverage of the sample, accumulated with the radiance.
+ *   Holdouts, shadow catchers, and transparent backgrounds lower it
  */
+#define FILTER_NEGATIVE_BIT 0x80000000u
+#define SHADOW_CATCHER_BIT 0x04000000u
+#define FIRST_LOBE_SHIFT 24u
+#define BOUNCE_MASK 0xFFFFu
+
 struct RayPayload {
   vec4 origin;
   vec4 dir;
   vec4 radiance;
   uvec4 terminated;
+  vec2 cone;
+  float alpha;
+  uint padding;
 };
 
 struct Ray {
@@ -68,7 +115,8 @@ struct Intersection {
   uint materialIndex;
   uint emitter;
   float dist;
-  float padding_1; // not needed I think
+  // Index of the intersected ray, only used with ray compaction.
+  uint ray;
 };
 
 #endif // STRUCTS_H
@@ -0,0 +1,108 @@
+#ifndef SUBSURFACE_H
+#define SUBSURFACE_H
+
+/**
+ * Random walk subsurface scattering.
+ *
+ * Requires `medium.glsl`, `sampling.glsl`, and the scene traversal of
+ * `intersection_utils.glsl`.
+ */
+
+// Walks longer than this are considered absorbed.
+#define SUBSURFACE_MAX_STEPS 256u
+
+/**
+ * Medium yielding the multiple scattering albedo `color`, with a mean free
+ * path of `radius`.
+ *
+ * This method is based on:
+ *  - Practical and Controllable Subsurface Scattering for Production Path
+ *    Tracing, Chiang et al. 2016
+ */
+Medium
+subsurfaceMedium(vec3 color, vec3 radius)
+{
+  color = clamp(color, vec3(0.0), vec3(0.999));
+  vec3 s = 4.09712 + 4.20863 * color - sqrt(9.59217 + 41.6808 * color + 17.7126 * color * color);
+  vec3 albedo = 1.0 - s * s;
+
+  vec3 sigmaT = 1.0 / max(radius, vec3(1e-6));
+  Medium medium;
+  medium.scattering = sigmaT * albedo;
+  medium.absorption = sigmaT - medium.scattering;
+  medium.anisotropy = 0.0;
+  return medium;
+}
+
+/**
+ * Walks inside a closed mesh, until the path refracts out of it.
+ *
+ * The surface is a smooth dielectric boundary: the walk goes on after an
+ * internal reflection, with the probability given by the Fresnel term. Both
+ * events are sampled exactly, the weight is thus `1`.
+ *
+ * @param medium The medium enclosed by the mesh
+ * @param ior The index of refraction of the mesh
+ * @param position The entry point, updated to the exit point
+ * @param dir The entry direction, updated to the refracted direction
+ *   leaving the mesh
+ * @param exitNormal The geometric normal at the exit point, facing outside
+ * @param throughput The path throughput, weighted by the walk
+ * @param seed The current value of a seed variable
+ *
+ * @return `false` if the walk didn't reach the surface
+ */
+bool
+randomWalkSubsurface(
+  const Medium medium,
+  float ior,
+  inout vec3 position,
+  inout vec3 dir,
+  out vec3 exitNormal,
+  inout vec3 throughput,
+  inout uint seed
+)
+{
+  exitNormal = vec3(0.0);
+  for (uint i = 0u; i < SUBSURFACE_MAX_STEPS; ++i)
+  {
+    Ray walk;
+    walk.origin = position;
+    walk.dir = dir;
+    Intersection hit = sceneHit(walk);
+    // Open meshes can't be exited.
+    if (hit.instance == INVALID_UINT) return false;
+
+    vec3 weight;
+    float t = sampleFreeFlight(medium, hit.dist, weight, seed);
+    throughput *= weight;
+    position += t * dir;
+    if (t < hit.dist)
+    {
+      dir = sampleHenyeyGreenstein(dir, medium.anisotropy, seed);
+      continue;
+    }
+
+    Instance instance = instances[hit.instance];
+    Primitive primitive = extractPrimitive(instance, hit);
+    vec3 normal = cross(
+      primitive.v1.position.xyz - primitive.v0.position.xyz,
+      primitive.v2.position.xyz - primitive.v0.position.xyz
+    );
+    normal = normalize(transformDirection(normal, instance.modelToWorld));
+    exitNormal = dot(normal, dir) > 0.0 ? normal : - normal;
+
+    // Chiang 2016: the walk only leaves through the dielectric boundary.
+    if (rand(seed) < FresnelDielectric(dot(exitNormal, dir), ior))
+    {
+      dir = reflect(dir, exitNormal);
+      position -= exitNormal * 1e-4;
+      continue;
+    }
+    dir = refract(dir, - exitNormal, ior);
+    return true;
+  }
+  return false;
+}
+
+#endif // SUBSURFACE_H
@@ -1,30 +1,52 @@
 #ifndef TEXTURE_UTILS_H
 #define TEXTURE_UTILS_H
 
+// Flag of procedural textures, see `imports/procedural.glsl`.
+#define PROCEDURAL_TEXTURE_BIT 0x80000000u
+
 void
-fetchBounds(uint textureIndex, out vec4 bounds, out float layer)
+fetchBounds(uint textureIndex, out vec4 bounds, out float layer, out float mipCount)
 {
   uvec4 data = texelFetch(usampler1D(textureInfo, samplerNearest), int(textureIndex), 0);
   layer = float((data.w & 0xFF000000) >> 24);
-  bounds = vec4(float(data.x), float(data.y), float(data.z), float(data.w & 0x00FFFFFF));
+  mipCount = float((data.z & 0xFF000000) >> 24);
+  bounds = vec4(float(data.x), float(data.y), float(data.z & 0x00FFFFFF), float(data.w & 0x00FFFFFF));
 }
 
+/**
+ * Fetch a texture of the atlas, at the level of detail of a ray cone.
+ *
+ * @param footprint Level of detail of the ray cone for a one texel texture,
+ *   the size of the texture is added here
+ */
 vec4
-fetchTexture(uint textureIndex, vec2 uv)
+fetchTexture(uint textureIndex, vec2 uv, float footprint)
 {
+  if ((textureIndex & PROCEDURAL_TEXTURE_BIT) != 0u)
+  {
+    #ifdef PROCEDURAL_TEXTURES
+    return evaluateProceduralTexture(textureIndex, uv);
+    #else
+    // Without node texture, the index is out of the atlas.
+    return vec4(1.0);
+    #endif
+  }
+
   uv = mod(uv, vec2(1.0, 1.0));
   // @todo: optimize away.
   vec2 atlasSize = vec2(textureSize(textureAtlas, 0).xy);
   float layer = 0.0;
+  float mipCount = 0.0;
   vec4 bounds = vec4(0.0);
-  fetchBounds(textureIndex, bounds, layer);
+  fetchBounds(textureIndex, bounds, layer, mipCount);
+  float lod = clamp(footprint + 0.5 * log2(bounds.z * bounds.w), 0.0, max(0.0, mipCount - 1.0));
   bounds.xy /= atlasSize;
   bounds.zw /= atlasSize;
-  // linear sampling
+  // Linear sampling, blocks are surrounded by a gutter.
   return textureLod(
-    sampler2DArray(textureAtlas, samplerNearest),
+    sampler2DArray(textureAtlas, samplerLinear),
     vec3(bounds.xy + (uv * bounds.zw), layer),
-    0.0
+    lod
   );
 }
 
@@ -0,0 +1,149 @@
+#ifndef TONEMAPPING_H
+#define TONEMAPPING_H
+
+/**
+ * Tonemapping operators, mirrored on the CPU by `albedo_rtx::tonemapping`.
+ * Changes must be applied to both.
+ *
+ * Requires `imports/colorspace.glsl`.
+ */
+
+#define TONEMAPPER_NONE 0u
+#define TONEMAPPER_REINHARD 1u
+#define TONEMAPPER_ACES_FITTED 2u
+#define TONEMAPPER_AGX 3u
+#define TONEMAPPER_PBR_NEUTRAL 4u
+#define TONEMAPPER_ACES_FILM 5u
+
+#define MIDDLE_GREY 0.18
+
+struct Tonemapping
+{
+  uint tonemapper;
+  float whitePoint;
+  float contrast;
+  uint padding;
+};
+
+/**
+ * Extended Reinhard, mapping `white` to `1`.
+ */
+vec3
+tonemapReinhard(vec3 x, float white)
+{
+  return x * (1.0 + x / (white * white)) / (1.0 + x);
+}
+
+/**
+ * ACES fitted by Stephen Hill, including the input and output transforms.
+ */
+vec3
+tonemapACESFitted(vec3 color)
+{
+  const mat3 inputMatrix = mat3(
+    0.59719, 0.07600, 0.02840,
+    0.35458, 0.90834, 0.13383,
+    0.04823, 0.01566, 0.83777
+  );
+  const mat3 outputMatrix = mat3(
+    1.60475, -0.10208, -0.00327,
+    -0.53108, 1.10813, -0.07276,
+    -0.07367, -0.00605, 1.07602
+  );
+  color = inputMatrix * color;
+  vec3 a = color * (color + 0.0245786) - 0.000090537;
+  vec3 b = color * (0.983729 * color + 0.432951) + 0.238081;
+  return clamp(outputMatrix * (a / b), 0.0, 1.0);
+}
+
+/**
+ * AgX by Troy Sobotka, with the polynomial fit of Benjamin Wrensch.
+ */
+vec3
+tonemapAgX(vec3 color)
+{
+  const mat3 inset = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104
+  );
+  const mat3 outset = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+  );
+  const float minEv = -12.47393;
+  const float maxEv = 4.026069;
+
+  color = inset * color;
+  color = clamp(log2(max(color, vec3(1e-10))), vec3(minEv), vec3(maxEv));
+  color = (color - minEv) / (maxEv - minEv);
+
+  vec3 x2 = color * color;
+  vec3 x4 = x2 * x2;
+  color = 15.5 * x4 * x2 - 40.14 * x4 * color + 31.96 * x4
+    - 6.868 * x2 * color + 0.4298 * x2 + 0.1191 * color - 0.00232;
+
+  color = outset * color;
+  // Back to linear, the display encoding is applied afterward.
+  return clamp(pow(max(color, vec3(0.0)), vec3(2.2)), 0.0, 1.0);
+}
+
+/**
+ * Khronos PBR Neutral.
+ */
+vec3
+tonemapPBRNeutral(vec3 color)
+{
+  const float startCompression = 0.8 - 0.04;
+  const float desaturation = 0.15;
+
+  float x = min(color.r, min(color.g, color.b));
+  float offset = x < 0.08 ? x - 6.25 * x * x : 0.04;
+  color -= offset;
+
+  float peak = max(color.r, max(color.g, color.b));
+  if (peak < startCompression) return color;
+
+  const float d = 1.0 - startCompression;
+  float newPeak = 1.0 - d * d / (peak + d - startCompression);
+  color *= newPeak / peak;
+
+  float g = 1.0 - 1.0 / (desaturation * (peak - newPeak) + 1.0);
+  return mix(color, vec3(newPeak), g);
+}
+
+/**
+ * Maps linear radiance to `[0; 1]`, before the display encoding.
+ *
+ * The contrast is applied around middle grey. `None` and `Reinhard` map the
+ * white point to `1`, other operators scale their input by its inverse.
+ */
+vec3
+tonemap(vec3 color, Tonemapping parameters)
+{
+  color = max(color, vec3(0.0));
+  color = MIDDLE_GREY * pow(color / MIDDLE_GREY, vec3(parameters.contrast));
+
+  float white = max(parameters.whitePoint, 1e-4);
+  if (parameters.tonemapper == TONEMAPPER_REINHARD) {
+    return clamp(tonemapReinhard(color, white), 0.0, 1.0);
+  }
+
+  color /= white;
+  if (parameters.tonemapper == TONEMAPPER_ACES_FILM) {
+    return ACESFilmTonemapping(color);
+  }
+  if (parameters.tonemapper == TONEMAPPER_ACES_FITTED) {
+    return tonemapACESFitted(color);
+  }
+  if (parameters.tonemapper == TONEMAPPER_AGX) {
+    return tonemapAgX(color);
+  }
+  if (parameters.tonemapper == TONEMAPPER_PBR_NEUTRAL) {
+    return clamp(tonemapPBRNeutral(color), 0.0, 1.0);
+  }
+  return clamp(color, 0.0, 1.0);
+}
+
+#endif // TONEMAPPING_H
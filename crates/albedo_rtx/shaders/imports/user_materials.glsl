@@ -0,0 +1,25 @@
+#ifndef USER_MATERIALS_H
+#define USER_MATERIALS_H
+
+/**
+ * Dispatch to the materials registered by applications.
+ *
+ * This file is replaced by the code generated by `UserMaterials`. This
+ * default version has no user material.
+ */
+
+vec3
+evaluateUserMaterial(uint type, const UserSurface surface, const vec3 wo, const vec3 wi)
+{
+  return vec3(0.0);
+}
+
+vec3
+sampleUserMaterial(uint type, const UserSurface surface, const vec3 wo, out float pdf, out uint lobe, inout uint seed)
+{
+  pdf = 0.0;
+  lobe = LOBE_DIFFUSE;
+  return surface.normal;
+}
+
+#endif // USER_MATERIALS_H
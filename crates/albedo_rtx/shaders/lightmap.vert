@@ -1,9 +1,10 @@
 #version 450
 
 #include "imports/structures.glsl"
+#include "imports/packing.glsl"
 
 layout(location=0) in vec4 vPosition;
-layout(location=1) in vec4 vNormal;
+layout(location=1) in uvec4 vAttributes;
 
 layout(location=0) out vec3 vPositionWorld;
 layout(location=1) out vec3 vNormalWorld;
@@ -18,9 +19,9 @@ void main() {
   /* Position in World space */
   vPositionWorld = (instance.modelToWorld * vec4(vPosition.xyz, 1.0)).xyz;
   /* Normal in World space */
-  vNormalWorld = (instance.modelToWorld * vec4(vNormal.xyz, 0.0)).xyz;
+  vNormalWorld = (instance.modelToWorld * vec4(unpackOctahedral(vAttributes.x), 0.0)).xyz;
   /* UV */
-  vUv = vec2(vPosition.w, vNormal.w);
+  vUv = vec2(vPosition.w, uintBitsToFloat(vAttributes.z));
   vUv *= 0.99999;
   vUv = mod(vUv, vec2(1.0, 1.0));
 
@@ -0,0 +1,136 @@
+// Material sort.
+//
+// Counting sort of the compacted intersections, keyed by material index.
+// Misses all go in the last bin. The sorted intersections still carry the
+// index of their ray, and can thus be shaded as is.
+//
+// Entry points must run in order, with the bins cleared beforehand:
+// - `histogram`: counts the intersections of each bin
+// - `scan`: turns the counts into offsets, using a single workgroup
+// - `scatter`: copies each intersection at the offset of its bin
+//
+// When sorting is disabled, `copy` runs alone and keeps the original order.
+
+const INVALID_UINT: u32 = 0xFFFFFFFFu;
+
+struct Intersection {
+  uv: vec2<f32>,
+  index: u32,
+  instance: u32,
+  material_index: u32,
+  emitter: u32,
+  dist: f32,
+  ray: u32,
+}
+
+struct ActiveRays {
+  workgroups_x: u32,
+  workgroups_y: u32,
+  workgroups_z: u32,
+  count: u32,
+  indices: array<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> intersections: array<Intersection>;
+@group(0) @binding(1) var<storage, read> active_rays: ActiveRays;
+@group(0) @binding(2) var<storage, read_write> bins: array<atomic<u32>>;
+@group(0) @binding(3) var<storage, read_write> sorted_intersections: array<Intersection>;
+
+const SCAN_SIZE: u32 = 256u;
+var<workgroup> totals: array<u32, SCAN_SIZE>;
+
+fn active_ray_slot(workgroup_id: vec3<u32>, num_workgroups: vec3<u32>, local_index: u32) -> u32 {
+  return (workgroup_id.y * num_workgroups.x + workgroup_id.x) * 64u + local_index;
+}
+
+fn bin(intersection: Intersection) -> u32 {
+  let miss = arrayLength(&bins) - 1u;
+  if (intersection.instance == INVALID_UINT) {
+    return miss;
+  }
+  return min(intersection.material_index, miss - 1u);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn histogram(
+  @builtin(workgroup_id) workgroup_id: vec3<u32>,
+  @builtin(num_workgroups) num_workgroups: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  let slot = active_ray_slot(workgroup_id, num_workgroups, local_index);
+  if (slot >= active_rays.count || slot >= arrayLength(&intersections)) {
+    return;
+  }
+  atomicAdd(&bins[bin(intersections[slot])], 1u);
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn scan(@builtin(local_invocation_index) local_index: u32) {
+  // Each invocation scans a contiguous chunk of bins.
+  let bin_count = arrayLength(&bins);
+  let chunk = (bin_count + SCAN_SIZE - 1u) / SCAN_SIZE;
+  let start = min(local_index * chunk, bin_count);
+  let end = min(start + chunk, bin_count);
+
+  var sum = 0u;
+  for (var i = start; i < end; i++) {
+    sum += atomicLoad(&bins[i]);
+  }
+  totals[local_index] = sum;
+  workgroupBarrier();
+
+  // Inclusive scan of the chunk totals.
+  for (var stride = 1u; stride < SCAN_SIZE; stride <<= 1u) {
+    var value = totals[local_index];
+    if (local_index >= stride) {
+      value += totals[local_index - stride];
+    }
+    workgroupBarrier();
+    totals[local_index] = value;
+    workgroupBarrier();
+  }
+
+  var offset = totals[local_index] - sum;
+  for (var i = start; i < end; i++) {
+    let count = atomicLoad(&bins[i]);
+    atomicStore(&bins[i], offset);
+    offset += count;
+  }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn scatter(
+  @builtin(workgroup_id) workgroup_id: vec3<u32>,
+  @builtin(num_workgroups) num_workgroups: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  let slot = active_ray_slot(workgroup_id, num_workgroups, local_index);
+  if (slot >= arrayLength(&sorted_intersections)) {
+    return;
+  }
+  if (slot >= active_rays.count) {
+    // Let the shading pass know this invocation has nothing to do.
+    sorted_intersections[slot].ray = INVALID_UINT;
+    return;
+  }
+  let intersection = intersections[slot];
+  let destination = atomicAdd(&bins[bin(intersection)], 1u);
+  sorted_intersections[destination] = intersection;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn copy(
+  @builtin(workgroup_id) workgroup_id: vec3<u32>,
+  @builtin(num_workgroups) num_workgroups: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  let slot = active_ray_slot(workgroup_id, num_workgroups, local_index);
+  if (slot >= arrayLength(&sorted_intersections)) {
+    return;
+  }
+  if (slot >= active_rays.count) {
+    sorted_intersections[slot].ray = INVALID_UINT;
+    return;
+  }
+  sorted_intersections[slot] = intersections[slot];
+}
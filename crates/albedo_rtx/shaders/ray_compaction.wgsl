@@ -0,0 +1,53 @@
+// Ray compaction.
+//
+// Appends the index of each ray still alive to the active ray list.
+//
+// The header of the list doubles as indirect dispatch arguments, with one
+// `8x8` workgroup for every 64 active rays. The header must be cleared
+// before this pass.
+
+// Dispatch limit of a single dimension.
+const MAX_WORKGROUPS: u32 = 65535u;
+
+struct RayPayload {
+  origin: vec4<f32>,
+  dir: vec4<f32>,
+  radiance: vec4<f32>,
+  terminated: vec4<u32>,
+  cone: vec2<f32>,
+  alpha: f32,
+  padding: u32,
+}
+
+struct ActiveRays {
+  workgroups_x: atomic<u32>,
+  workgroups_y: atomic<u32>,
+  workgroups_z: u32,
+  count: atomic<u32>,
+  indices: array<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> rays: array<RayPayload>;
+@group(0) @binding(1) var<storage, read_write> active_rays: ActiveRays;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(
+  @builtin(global_invocation_id) id: vec3<u32>,
+  @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+  let index = id.y * 8u * num_workgroups.x + id.x;
+  if (index == 0u) {
+    active_rays.workgroups_z = 1u;
+  }
+  if (index >= arrayLength(&rays) || rays[index].terminated.x > 0u) {
+    return;
+  }
+
+  let slot = atomicAdd(&active_rays.count, 1u);
+  active_rays.indices[slot] = index;
+
+  // Workgroups are spread on `y` past the dispatch limit of `x`.
+  let workgroups = slot / 64u + 1u;
+  atomicMax(&active_rays.workgroups_x, min(workgroups, MAX_WORKGROUPS));
+  atomicMax(&active_rays.workgroups_y, (workgroups - 1u) / MAX_WORKGROUPS + 1u);
+}
@@ -6,15 +6,82 @@ use crate::{uniforms::Instance, BVHNode, BVHPrimitive, Vertex};
 pub struct MeshDescriptor<'a> {
     pub positions: pas::Slice<'a, [f32; 4]>,
     pub normals: Option<pas::Slice<'a, [f32; 3]>>,
+    /// Tangents in `xyz`, and bitangent handedness in `w`.
+    ///
+    /// When `None`, tangents are generated if normals and texture
+    /// coordinates are available.
+    pub tangents: Option<pas::Slice<'a, [f32; 4]>>,
     pub texcoords0: Option<pas::Slice<'a, [f32; 2]>>,
 }
 
+impl<'a> MeshDescriptor<'a> {
+    /// Create a descriptor without tangents, generated when possible.
+    pub fn new(
+        positions: pas::Slice<'a, [f32; 4]>,
+        normals: Option<pas::Slice<'a, [f32; 3]>>,
+        texcoords0: Option<pas::Slice<'a, [f32; 2]>>,
+    ) -> Self {
+        Self {
+            positions,
+            normals,
+            tangents: None,
+            texcoords0,
+        }
+    }
+
+    pub fn with_tangents(self, tangents: pas::Slice<'a, [f32; 4]>) -> Self {
+        Self {
+            tangents: Some(tangents),
+            ..self
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct IndexedMeshDescriptor<'a> {
     pub mesh: MeshDescriptor<'a>,
     pub indices: &'a [u32],
 }
 
+/// Triangle list, as stored in [`BLASArray::vertices`], used to
+/// run MikkTSpace.
+struct TangentGenerator<'a> {
+    vertices: &'a mut [Vertex],
+}
+
+impl<'a> mikktspace::Geometry for TangentGenerator<'a> {
+    fn num_faces(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        *self.vertices[face * 3 + vert].position()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[face * 3 + vert].normal()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[face * 3 + vert].uv()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.vertices[face * 3 + vert].set_tangent(&tangent);
+    }
+}
+
+/// Generate MikkTSpace tangents for a non-indexed triangle list.
+///
+/// Vertices are left untouched if generation fails, e.g., on degenerate UVs.
+fn generate_tangents(vertices: &mut [Vertex]) {
+    mikktspace::generate_tangents(&mut TangentGenerator { vertices });
+}
+
 /// Node, vertex, and index offset of an entry
 ///
 /// This is used to retrieve a flattened BVH into a buffer
@@ -72,16 +139,24 @@ impl BLASArray {
         }
         if let Some(normals) = mesh.normals {
             for i in 0..normals.len() {
-                let normal = &normals[i];
-                vertices[i].normal = [normal[0], normal[1], normal[2], 0.0];
+                vertices[i].set_normal(&normals[i]);
             }
         }
         if let Some(texcoord) = mesh.texcoords0 {
             for i in 0..texcoord.len() {
-                let uv = &texcoord[i];
-                vertices[i].position[3] = uv[0];
-                vertices[i].normal[3] = uv[1];
+                vertices[i].set_uv(&texcoord[i]);
+            }
+        }
+        match mesh.tangents {
+            Some(tangents) => {
+                for i in 0..tangents.len() {
+                    vertices[i].set_tangent(&tangents[i]);
+                }
+            }
+            None if mesh.normals.is_some() && mesh.texcoords0.is_some() => {
+                generate_tangents(vertices);
             }
+            None => (),
         }
         let bvh = cwbvh::BVH::new_hq(mesh.positions);
         self.nodes.extend(bvh.nodes());
@@ -107,17 +182,25 @@ impl BLASArray {
         }
         if let Some(normals) = desc.mesh.normals {
             for (i, index) in desc.indices.into_iter().enumerate() {
-                let normal = &normals[*index as usize];
-                vertices[i].normal = [normal[0], normal[1], normal[2], 0.0];
+                vertices[i].set_normal(&normals[*index as usize]);
             }
         }
         if let Some(uvs) = desc.mesh.texcoords0 {
             for (i, index) in desc.indices.into_iter().enumerate() {
-                let uv = &uvs[*index as usize];
-                vertices[i].position[3] = uv[0];
-                vertices[i].normal[3] = uv[1];
+                vertices[i].set_uv(&uvs[*index as usize]);
             }
         }
+        match desc.mesh.tangents {
+            Some(tangents) => {
+                for (i, index) in desc.indices.into_iter().enumerate() {
+                    vertices[i].set_tangent(&tangents[*index as usize]);
+                }
+            }
+            None if desc.mesh.normals.is_some() && desc.mesh.texcoords0.is_some() => {
+                generate_tangents(vertices);
+            }
+            None => (),
+        }
 
         let vertices: &[Vertex] = &self.vertices[start..];
         let positions: pas::Slice<[f32; 4]> = pas::Slice::new(vertices, 0);
@@ -136,6 +219,7 @@ impl BLASArray {
             bvh_root_index: entry.node,
             vertex_root_index: entry.vertex,
             bvh_primitive_index: entry.primitive,
+            ..Default::default()
         });
     }
 }
@@ -0,0 +1,391 @@
+//! CPU port of the principled BSDF of `shaders/imports/sampling.glsl`.
+//!
+//! The functions mirror the shader one to one, and are used to validate the
+//! lobes, e.g., with a white furnace test. All directions are expressed in the
+//! local shading frame, where `z` is the normal.
+
+use glam::Vec3;
+
+use crate::uniforms::Material;
+
+pub(crate) const EPSILON: f32 = 0.00000001;
+const PI: f32 = std::f32::consts::PI;
+pub(crate) const TWO_PI: f32 = 2.0 * std::f32::consts::PI;
+
+/// Lobes of the BSDF, see `LOBE_*` in the shader.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse = 0,
+    Glossy = 1,
+    Transmission = 2,
+}
+
+/// Material data after processing, see `MaterialState` in the shader.
+#[derive(Clone, Copy, Debug)]
+pub struct MaterialState {
+    pub albedo: Vec3,
+    pub metallic: f32,
+    pub f0: Vec3,
+    pub roughness: f32,
+    pub transmission: f32,
+    pub ior: f32,
+    pub sheen_color: Vec3,
+    pub sheen_roughness: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub anisotropy: f32,
+    pub subsurface: f32,
+}
+
+impl MaterialState {
+    /// Process a material the same way `shading.comp` does, textures aside.
+    pub fn from_material(material: &Material, front_face: bool) -> Self {
+        let albedo = srgb_to_linear(material.color.truncate());
+        let metallic = material.reflectivity;
+        let perceptual_roughness = material.roughness.max(EPSILON);
+
+        let tint = albedo / luminance(albedo).max(EPSILON);
+        let dielectric_f0 = Vec3::splat(0.04 * material.specular.clamp(0.0, 1.0))
+            * Vec3::ONE.lerp(tint, material.specular_tint);
+
+        let (clearcoat, sheen_color) = if front_face {
+            (
+                material.clearcoat.clamp(0.0, 1.0),
+                material.sheen_color.clamp(Vec3::ZERO, Vec3::ONE),
+            )
+        } else {
+            (0.0, Vec3::ZERO)
+        };
+
+        Self {
+            albedo,
+            metallic,
+            f0: dielectric_f0.lerp(albedo, metallic).min(Vec3::ONE),
+            roughness: (perceptual_roughness * perceptual_roughness).max(EPSILON),
+            transmission: material.transmission * (1.0 - metallic),
+            ior: material.ior.max(1.0),
+            sheen_color,
+            sheen_roughness: (material.sheen_roughness * material.sheen_roughness).max(0.07),
+            clearcoat,
+            clearcoat_roughness: (material.clearcoat_roughness * material.clearcoat_roughness)
+                .max(EPSILON),
+            anisotropy: material.anisotropy.clamp(0.0, 1.0),
+            subsurface: if front_face {
+                material.subsurface.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+pub fn srgb_to_linear(color: Vec3) -> Vec3 {
+    color * (color * (color * 0.305_306 + 0.682_171_1) + 0.012_522_878)
+}
+
+pub fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+pub fn wang_hash(seed: &mut u32) -> u32 {
+    *seed = (*seed ^ 61) ^ (*seed >> 16);
+    *seed = seed.wrapping_mul(9);
+    *seed ^= *seed >> 4;
+    *seed = seed.wrapping_mul(0x27d4eb2d);
+    *seed ^= *seed >> 15;
+    *seed
+}
+
+pub fn rand(seed: &mut u32) -> f32 {
+    wang_hash(seed) as f32 / 4294967296.0
+}
+
+pub fn reflect(i: Vec3, n: Vec3) -> Vec3 {
+    i - 2.0 * n.dot(i) * n
+}
+
+pub fn refract(i: Vec3, n: Vec3, eta: f32) -> Vec3 {
+    let n_dot_i = n.dot(i);
+    let k = 1.0 - eta * eta * (1.0 - n_dot_i * n_dot_i);
+    if k < 0.0 {
+        Vec3::ZERO
+    } else {
+        eta * i - (eta * n_dot_i + k.sqrt()) * n
+    }
+}
+
+pub fn random_cosine_weighted_vector(seed: &mut u32) -> Vec3 {
+    let theta = rand(seed) * TWO_PI;
+    let r = rand(seed).max(EPSILON);
+    let r_len = (1.0 - r).max(EPSILON).sqrt();
+    Vec3::new(theta.cos() * r_len, theta.sin() * r_len, r.sqrt())
+}
+
+pub fn schlick_fresnel(u: f32) -> f32 {
+    let m = (1.0 - u).clamp(0.0, 1.0);
+    let m2 = m * m;
+    m2 * m2 * m
+}
+
+pub fn fresnel_schlick(f0: Vec3, cos_theta: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * schlick_fresnel(cos_theta)
+}
+
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let sin2_theta_t = eta * eta * (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let rs = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let rp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+pub fn d_ggx_aniso(h: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    let h = Vec3::new(h.x / alpha_x, h.y / alpha_y, h.z);
+    let t = h.dot(h);
+    1.0 / (PI * alpha_x * alpha_y * t * t).max(EPSILON)
+}
+
+pub fn lambda_ggx_aniso(v: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    let a2_tan2 =
+        (alpha_x * alpha_x * v.x * v.x + alpha_y * alpha_y * v.y * v.y) / (v.z * v.z).max(EPSILON);
+    0.5 * (-1.0 + (1.0 + a2_tan2).sqrt())
+}
+
+pub fn random_visible_microfacet_ggx(v: Vec3, alpha_x: f32, alpha_y: f32, seed: &mut u32) -> Vec3 {
+    let vh = Vec3::new(alpha_x * v.x, alpha_y * v.y, v.z).normalize();
+    let lensq = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if lensq > 0.0 {
+        Vec3::new(-vh.y, vh.x, 0.0) / lensq.sqrt()
+    } else {
+        Vec3::X
+    };
+    let t2 = vh.cross(t1);
+
+    let r = rand(seed).sqrt();
+    let phi = TWO_PI * rand(seed);
+    let p1 = r * phi.cos();
+    let mut p2 = r * phi.sin();
+    let s = 0.5 * (1.0 + vh.z);
+    p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+    Vec3::new(alpha_x * nh.x, alpha_y * nh.y, nh.z.max(EPSILON)).normalize()
+}
+
+pub fn sample_diffuse_lambert(seed: &mut u32) -> Vec3 {
+    random_cosine_weighted_vector(seed)
+}
+
+pub fn eval_diffuse_lambert(l: Vec3) -> f32 {
+    l.z.max(0.0) / PI
+}
+
+pub fn pdf_diffuse_lambert(l: Vec3) -> f32 {
+    l.z.max(0.0) / PI
+}
+
+pub fn sample_specular_ggx(v: Vec3, alpha_x: f32, alpha_y: f32, seed: &mut u32) -> Vec3 {
+    let h = random_visible_microfacet_ggx(v, alpha_x, alpha_y, seed);
+    reflect(-v, h)
+}
+
+pub fn eval_specular_ggx(v: Vec3, l: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    if v.z <= EPSILON || l.z <= EPSILON {
+        return 0.0;
+    }
+    let h = (v + l).normalize();
+    let g2 =
+        1.0 / (1.0 + lambda_ggx_aniso(v, alpha_x, alpha_y) + lambda_ggx_aniso(l, alpha_x, alpha_y));
+    d_ggx_aniso(h, alpha_x, alpha_y) * g2 / (4.0 * v.z)
+}
+
+pub fn pdf_specular_ggx(v: Vec3, l: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    if v.z <= EPSILON || l.z <= EPSILON {
+        return 0.0;
+    }
+    let h = (v + l).normalize();
+    let g1 = 1.0 / (1.0 + lambda_ggx_aniso(v, alpha_x, alpha_y));
+    g1 * d_ggx_aniso(h, alpha_x, alpha_y) / (4.0 * v.z)
+}
+
+pub fn d_charlie(n_dot_h: f32, roughness: f32) -> f32 {
+    let inv_r = 1.0 / roughness;
+    let sin2 = (1.0 - n_dot_h * n_dot_h).max(0.0);
+    (2.0 + inv_r) * sin2.powf(0.5 * inv_r) / TWO_PI
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+pub fn l_charlie(x: f32, roughness: f32) -> f32 {
+    let t = (1.0 - roughness) * (1.0 - roughness);
+    let a = mix(21.5473, 25.3245, t);
+    let b = mix(3.82987, 3.32435, t);
+    let c = mix(0.19823, 0.16801, t);
+    let d = mix(-1.97760, -1.27393, t);
+    let e = mix(-4.32054, -4.85967, t);
+    a / (1.0 + b * x.powf(c)) + d * x + e
+}
+
+pub fn lambda_charlie(cos_theta: f32, roughness: f32) -> f32 {
+    if cos_theta < 0.5 {
+        l_charlie(cos_theta, roughness).exp()
+    } else {
+        (2.0 * l_charlie(0.5, roughness) - l_charlie(1.0 - cos_theta, roughness)).exp()
+    }
+}
+
+pub fn sample_sheen_charlie(seed: &mut u32) -> Vec3 {
+    random_cosine_weighted_vector(seed)
+}
+
+pub fn eval_sheen_charlie(v: Vec3, l: Vec3, roughness: f32) -> f32 {
+    if v.z <= EPSILON || l.z <= EPSILON {
+        return 0.0;
+    }
+    let h = (v + l).normalize();
+    let g2 = 1.0 / (1.0 + lambda_charlie(v.z, roughness) + lambda_charlie(l.z, roughness));
+    d_charlie(h.z, roughness) * g2 / (4.0 * v.z)
+}
+
+pub fn pdf_sheen_charlie(l: Vec3) -> f32 {
+    pdf_diffuse_lambert(l)
+}
+
+/// Directional albedo of the white sheen lobe, see `SHEEN_ALBEDO` in the shader.
+#[rustfmt::skip]
+const SHEEN_ALBEDO: [[f32; 16]; 8] = [
+    [0.9733, 0.9901, 0.9167, 0.7836, 0.6440, 0.5137, 0.3977, 0.2975, 0.2136, 0.1456, 0.0929, 0.0543, 0.0283, 0.0122, 0.0036, 0.0003],
+    [0.9065, 0.9252, 0.8665, 0.7513, 0.6285, 0.5122, 0.4070, 0.3143, 0.2344, 0.1674, 0.1131, 0.0711, 0.0407, 0.0200, 0.0073, 0.0009],
+    [0.8122, 0.8340, 0.7975, 0.7091, 0.6117, 0.5173, 0.4298, 0.3501, 0.2784, 0.2148, 0.1593, 0.1124, 0.0748, 0.0452, 0.0225, 0.0064],
+    [0.7766, 0.8017, 0.7795, 0.7069, 0.6244, 0.5433, 0.4670, 0.3961, 0.3304, 0.2699, 0.2145, 0.1646, 0.1219, 0.0850, 0.0528, 0.0249],
+    [0.7978, 0.8262, 0.8116, 0.7448, 0.6674, 0.5911, 0.5190, 0.4514, 0.3880, 0.3283, 0.2722, 0.2200, 0.1740, 0.1325, 0.0938, 0.0574],
+    [0.8519, 0.8830, 0.8709, 0.8038, 0.7258, 0.6491, 0.5768, 0.5090, 0.4451, 0.3846, 0.3270, 0.2726, 0.2243, 0.1798, 0.1377, 0.0971],
+    [0.9032, 0.9355, 0.9231, 0.8538, 0.7738, 0.6957, 0.6224, 0.5539, 0.4895, 0.4282, 0.3698, 0.3145, 0.2652, 0.2198, 0.1765, 0.1349],
+    [0.9017, 0.9337, 0.9220, 0.8546, 0.7771, 0.7016, 0.6312, 0.5655, 0.5038, 0.4452, 0.3891, 0.3359, 0.2886, 0.2451, 0.2036, 0.1638],
+];
+
+pub fn albedo_sheen_charlie(cos_theta: f32, roughness: f32) -> f32 {
+    let u = cos_theta.clamp(0.0, 1.0).sqrt() * 15.0;
+    let v = ((roughness - 0.07) / 0.93).clamp(0.0, 1.0).sqrt() * 7.0;
+    let (i, j) = ((u as usize).min(14), (v as usize).min(6));
+    let (fu, fv) = (u - i as f32, v - j as f32);
+    let e0 = mix(SHEEN_ALBEDO[j][i], SHEEN_ALBEDO[j][i + 1], fu);
+    let e1 = mix(SHEEN_ALBEDO[j + 1][i], SHEEN_ALBEDO[j + 1][i + 1], fu);
+    mix(e0, e1, fv)
+}
+
+/// Sample the rough dielectric BSDF.
+///
+/// Returns the sampled direction, its weight, i.e., `bsdf * cos / pdf`, and lobe.
+pub fn sample_dielectric(
+    v: Vec3,
+    mat: &MaterialState,
+    eta: f32,
+    seed: &mut u32,
+) -> (Vec3, Vec3, Lobe) {
+    let alpha = mat.roughness;
+    let h = random_visible_microfacet_ggx(v, alpha, alpha, seed);
+    let v_dot_h = v.dot(h);
+    let f = fresnel_dielectric(v_dot_h.abs(), eta);
+
+    let reflected = rand(seed) < f;
+    let l = if reflected {
+        reflect(-v, h)
+    } else {
+        refract(-v, h, eta)
+    };
+    let lobe = if reflected {
+        Lobe::Glossy
+    } else {
+        Lobe::Transmission
+    };
+
+    if v.z <= EPSILON || l.dot(l) < EPSILON || (reflected != (l.z > 0.0)) {
+        return (l, Vec3::ZERO, lobe);
+    }
+
+    let lm = Vec3::new(l.x, l.y, l.z.abs());
+    let mut weight = Vec3::splat(1.0 / (1.0 + lambda_ggx_aniso(lm, alpha, alpha)));
+    if !reflected {
+        weight *= mat.albedo * eta * eta;
+    }
+    (l, weight, lobe)
+}
+
+/// Sample the layered principled BSDF.
+///
+/// Returns the sampled direction, its weight, i.e., `bsdf * cos / pdf`, and lobe.
+pub fn sample_principled(
+    v: Vec3,
+    mat: &MaterialState,
+    eta: f32,
+    seed: &mut u32,
+) -> (Vec3, Vec3, Lobe) {
+    if v.z <= EPSILON {
+        return (Vec3::Z, Vec3::ZERO, Lobe::Glossy);
+    }
+
+    let coat_probability = mat.clearcoat * fresnel_schlick(Vec3::splat(0.04), v.z).x;
+    let sheen_probability =
+        mat.sheen_color.max_element() * albedo_sheen_charlie(v.z, mat.sheen_roughness);
+
+    if rand(seed) < coat_probability {
+        let alpha = mat.clearcoat_roughness;
+        let l = sample_specular_ggx(v, alpha, alpha, seed);
+        let pdf = pdf_specular_ggx(v, l, alpha, alpha);
+        let weight = if pdf > EPSILON {
+            Vec3::splat(eval_specular_ggx(v, l, alpha, alpha) / pdf)
+        } else {
+            Vec3::ZERO
+        };
+        (l, weight, Lobe::Glossy)
+    } else if rand(seed) < sheen_probability {
+        let l = sample_sheen_charlie(seed);
+        let pdf = pdf_sheen_charlie(l);
+        let weight = if pdf > EPSILON {
+            (mat.sheen_color / sheen_probability) * eval_sheen_charlie(v, l, mat.sheen_roughness)
+                / pdf
+        } else {
+            Vec3::ZERO
+        };
+        (l, weight, Lobe::Diffuse)
+    } else if rand(seed) < mat.transmission {
+        sample_dielectric(v, mat, eta, seed)
+    } else {
+        let alpha_x = mix(mat.roughness, 1.0, mat.anisotropy * mat.anisotropy);
+        let alpha_y = mat.roughness;
+
+        let specular = fresnel_schlick(mat.f0, v.z);
+        let specular_probability = specular.max_element();
+        if rand(seed) < specular_probability {
+            let l = sample_specular_ggx(v, alpha_x, alpha_y, seed);
+            let pdf = pdf_specular_ggx(v, l, alpha_x, alpha_y);
+            let weight = if pdf > EPSILON {
+                (specular / specular_probability) * eval_specular_ggx(v, l, alpha_x, alpha_y) / pdf
+            } else {
+                Vec3::ZERO
+            };
+            (l, weight, Lobe::Glossy)
+        } else {
+            let l = sample_diffuse_lambert(seed);
+            let pdf = pdf_diffuse_lambert(l);
+            if rand(seed) < mat.subsurface {
+                // Diffuse transmission, the color is given by the random walk.
+                let l = Vec3::new(l.x, l.y, -l.z);
+                return (l, Vec3::splat(1.0 - mat.metallic), Lobe::Diffuse);
+            }
+            let weight = if pdf > EPSILON {
+                mat.albedo * (1.0 - mat.metallic) * eval_diffuse_lambert(l) / pdf
+            } else {
+                Vec3::ZERO
+            };
+            (l, weight, Lobe::Diffuse)
+        }
+    }
+}
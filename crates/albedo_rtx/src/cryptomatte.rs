@@ -0,0 +1,144 @@
+//! Cryptomatte ID mattes.
+//!
+//! Instance and material names are hashed, and the coverage of the hashes
+//! is accumulated per pixel by [`crate::passes::CryptomattePass`].
+//!
+//! The manifest and metadata follow the [Cryptomatte specification],
+//! and can be written alongside the EXR layers.
+//!
+//! [Cryptomatte specification]: https://github.com/Psyop/Cryptomatte/blob/master/specification/cryptomatte_specification.pdf
+
+use std::collections::BTreeMap;
+
+/// MurmurHash3, 32 bits x86 variant.
+pub fn murmur3_32(bytes: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mix = |mut k: u32| {
+        k = k.wrapping_mul(C1);
+        k = k.rotate_left(15);
+        k.wrapping_mul(C2)
+    };
+
+    let mut hash = seed;
+    let mut chunks = bytes.chunks_exact(4);
+    for chunk in &mut chunks {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        hash ^= mix(k);
+        hash = hash.rotate_left(13);
+        hash = hash.wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, &byte| (k << 8) | byte as u32);
+        hash ^= mix(k);
+    }
+
+    hash ^= bytes.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^ (hash >> 16)
+}
+
+/// Cryptomatte hash of a name.
+///
+/// The hash is stored as float bits in the mattes, it's thus altered
+/// to never be a denormal, an infinite, or a NaN.
+pub fn cryptomatte_hash(name: &str) -> u32 {
+    let hash = murmur3_32(name.as_bytes(), 0);
+    let exponent = (hash >> 23) & 0xFF;
+    if exponent == 0 || exponent == 0xFF {
+        hash ^ (1 << 23)
+    } else {
+        hash
+    }
+}
+
+/// Name to hash mapping of a Cryptomatte layer.
+#[derive(Clone, Debug, Default)]
+pub struct CryptomatteManifest {
+    entries: BTreeMap<String, u32>,
+}
+
+impl CryptomatteManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a manifest from the names of the instances, or materials.
+    ///
+    /// The returned hashes are ordered like the names, and can be uploaded
+    /// as is for [`crate::passes::CryptomattePass`].
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> (Self, Vec<u32>) {
+        let mut manifest = Self::new();
+        let hashes = names.iter().map(|n| manifest.insert(n.as_ref())).collect();
+        (manifest, hashes)
+    }
+
+    /// Add a name, and return its hash.
+    pub fn insert(&mut self, name: &str) -> u32 {
+        let hash = cryptomatte_hash(name);
+        self.entries.insert(name.to_string(), hash);
+        hash
+    }
+
+    pub fn hash(&self, name: &str) -> Option<u32> {
+        self.entries.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Manifest as a JSON object, mapping each name to its hexadecimal hash.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(name, hash)| format!("\"{}\":\"{:08x}\"", escape_json(name), hash))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+
+    /// EXR header attributes of the layer `layer_name`, e.g., `CryptoObject`.
+    ///
+    /// The layer key is derived from the MurmurHash3 of the layer name,
+    /// the specification only requires it to be unique per file.
+    pub fn metadata(&self, layer_name: &str) -> Vec<(String, String)> {
+        let key = &format!("{:08x}", murmur3_32(layer_name.as_bytes(), 0))[..7];
+        let prefix = format!("cryptomatte/{}", key);
+        vec![
+            (format!("{}/name", prefix), layer_name.to_string()),
+            (format!("{}/hash", prefix), "MurmurHash3_32".to_string()),
+            (
+                format!("{}/conversion", prefix),
+                "uint32_to_float32".to_string(),
+            ),
+            (format!("{}/manifest", prefix), self.to_json()),
+        ]
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,8 +1,14 @@
 pub mod blas;
+pub mod bsdf;
+pub mod cryptomatte;
 pub mod layouts;
 pub mod macros;
 pub mod passes;
+pub mod procedural;
 pub mod shaders;
+pub mod spectrum;
+pub mod subsurface;
+pub mod tonemapping;
 pub mod uniforms;
 
 pub use blas::*;
@@ -24,6 +24,10 @@ impl ATrousPass {
     const RADIANCE_OUT_BINDING: u32 = 2;
     const SAMPLER_BINDING: u32 = 3;
 
+    /// Maximum number of iterations, the step size of the last one being
+    /// `2^15` pixels, i.e., larger than any frame.
+    pub const MAX_COUNT: u8 = 16;
+
     pub fn new(device: &wgpu::Device, processor: &ShaderCache) -> Self {
         let frame_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -110,6 +114,18 @@ impl ATrousPass {
         }
     }
 
+    /// Number of iterations, with the step size doubling at each one.
+    pub fn count(&self) -> u8 {
+        self.count
+    }
+
+    /// Set the number of iterations, clamped to `[1; MAX_COUNT]`.
+    ///
+    /// The output of the first iteration is retained, see [`Self::dispatch`].
+    pub fn set_count(&mut self, count: u8) {
+        self.count = count.clamp(1, Self::MAX_COUNT);
+    }
+
     pub fn create_frame_bind_groups(
         &self,
         device: &wgpu::Device,
@@ -167,6 +183,11 @@ impl ATrousPass {
         ]
     }
 
+    /// Filter the radiance, alternating between both bind groups.
+    ///
+    /// The output of the first iteration, written to `first_output`, is
+    /// copied to `retain`, e.g., as the history of the temporal accumulation.
+    /// The last iteration writes to `first_output` if the count is odd.
     pub fn dispatch(
         &self,
         encoder: &mut wgpu::CommandEncoder,
@@ -6,6 +6,16 @@ use crate::uniforms::{PerDrawUniforms, Ray};
 use albedo_backend::data::ShaderCache;
 use albedo_backend::gpu;
 
+/// Views of a target of the [`AccumulationPass`].
+///
+/// `radiance` holds the radiance summed over samples, and the sum of
+/// the weights in alpha. `alpha` holds the sum of the weighted alpha of
+/// the samples, and uses [`AccumulationPass::ALPHA_FORMAT`].
+pub struct AccumulationTarget<'a> {
+    pub radiance: &'a wgpu::TextureView,
+    pub alpha: &'a wgpu::TextureView,
+}
+
 pub struct AccumulationPass {
     bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::ComputePipeline,
@@ -19,6 +29,11 @@ impl AccumulationPass {
     const TEXTURE_BINDING: u32 = 2;
     const READ_TEXTURE_BINDING: u32 = 3;
     const SAMPLER_BINDING: u32 = 4;
+    const ALPHA_TEXTURE_BINDING: u32 = 5;
+    const READ_ALPHA_TEXTURE_BINDING: u32 = 6;
+
+    /// Format of [`AccumulationTarget::alpha`].
+    pub const ALPHA_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
 
     pub fn new(device: &wgpu::Device, processor: &ShaderCache) -> Self {
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -70,6 +85,26 @@ impl AccumulationPass {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::ALPHA_TEXTURE_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        format: Self::ALPHA_FORMAT,
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::READ_ALPHA_TEXTURE_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -114,13 +149,15 @@ impl AccumulationPass {
         }
     }
 
+    /// Create the bind group accumulating into `write`, from the previous
+    /// frame in `input`.
     pub fn create_frame_bind_groups(
         &self,
         device: &wgpu::Device,
         in_rays: gpu::StorageBufferSlice<Ray>,
         global_uniforms: gpu::UniformBufferSlice<PerDrawUniforms>,
-        write_view: &wgpu::TextureView,
-        input_view: &wgpu::TextureView,
+        write: AccumulationTarget,
+        input: AccumulationTarget,
         sampler: &wgpu::Sampler,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -133,7 +170,7 @@ impl AccumulationPass {
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::TEXTURE_BINDING,
-                    resource: wgpu::BindingResource::TextureView(write_view),
+                    resource: wgpu::BindingResource::TextureView(write.radiance),
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::PER_DRAW_STRUCT_BINDING,
@@ -141,12 +178,20 @@ impl AccumulationPass {
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::READ_TEXTURE_BINDING,
-                    resource: wgpu::BindingResource::TextureView(input_view),
+                    resource: wgpu::BindingResource::TextureView(input.radiance),
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::SAMPLER_BINDING,
                     resource: wgpu::BindingResource::Sampler(sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: Self::ALPHA_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(write.alpha),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::READ_ALPHA_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(input.alpha),
+                },
             ],
         })
     }
@@ -0,0 +1,278 @@
+use std::borrow::Cow;
+
+use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu;
+use bitflags::bitflags;
+use wgpu::naga::FastHashMap;
+
+use crate::get_dispatch_size;
+use crate::macros::path_separator;
+use crate::uniforms::{PerDrawUniforms, Ray};
+
+bitflags! {
+    /// Arbitrary output variables (AOVs) written by the [`super::ShadingPass`].
+    ///
+    /// Each AOV is stored in its own layer of an array texture, in the order
+    /// of the flags, see [`Aovs::layer`].
+    ///
+    /// Light path AOVs only split the radiance brought by each path:
+    /// - `DIRECT`: Emission seen from the camera, and light scattered once
+    /// - `INDIRECT`: Light scattered more than once
+    /// - `DIFFUSE`/`SPECULAR`: Light whose first surface hit sampled the diffuse,
+    ///   or the glossy and transmission lobes
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct Aovs: u32 {
+        /// Base color of the primary hit, in linear space.
+        const ALBEDO = 1 << 0;
+        /// World space shading normal of the primary hit.
+        const NORMAL = 1 << 1;
+        /// Distance from the camera to the primary hit.
+        const DEPTH = 1 << 2;
+        /// World space position of the primary hit.
+        const POSITION = 1 << 3;
+        /// Index of the instance of the primary hit, `-1` for misses.
+        const INSTANCE_ID = 1 << 4;
+        /// Index of the material of the primary hit, `-1` for misses.
+        const MATERIAL_ID = 1 << 5;
+        const DIRECT = 1 << 6;
+        const INDIRECT = 1 << 7;
+        const DIFFUSE = 1 << 8;
+        const SPECULAR = 1 << 9;
+    }
+}
+
+impl Aovs {
+    /// Number of layers needed to store the AOVs.
+    pub fn layer_count(&self) -> u32 {
+        self.bits().count_ones()
+    }
+
+    /// Layer of `aov` in the AOV textures, `None` if not part of the set.
+    pub fn layer(&self, aov: Aovs) -> Option<u32> {
+        if aov.bits().count_ones() != 1 || !self.contains(aov) {
+            return None;
+        }
+        Some((self.bits() & (aov.bits() - 1)).count_ones())
+    }
+
+    /// Add the defines selecting the AOVs, e.g., `AOV_ALBEDO`, to `defines`.
+    ///
+    /// The same defines must be used for every [`super::ShadingPass`] of a
+    /// frame, and for the [`AovAccumulationPass`].
+    pub fn insert_defines(&self, defines: &mut FastHashMap<String, String>) {
+        if self.is_empty() {
+            return;
+        }
+        defines.insert("AOV".into(), "".into());
+        for (name, aov) in self.iter_names() {
+            let layer = self.layer(aov).unwrap();
+            defines.insert(format!("AOV_{}", name), layer.to_string());
+        }
+    }
+
+    /// Create an array texture, with one layer per AOV.
+    ///
+    /// The texture can be used both for the AOVs written by the shading,
+    /// and for the accumulated ones.
+    pub fn create_texture(&self, device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("AOV Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: self.layer_count().max(1),
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+}
+
+/// Accumulates the AOVs written by the [`super::ShadingPass`] across frames.
+///
+/// Like the radiance, each layer accumulates the sum of the samples in `rgb`,
+/// and the sum of the filter weights in `a`. AOVs are thus averaged by dividing
+/// by `a`. Identifiers can't be averaged, they keep the first sample, with a
+/// weight of `1`.
+///
+/// A 2D view of a single layer of the output can be created to retrieve
+/// an AOV, e.g., for saving.
+pub struct AovAccumulationPass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl AovAccumulationPass {
+    const WORKGROUP_SIZE: (u32, u32, u32) = (8, 8, 1);
+
+    const RAY_BINDING: u32 = 0;
+    const PER_DRAW_STRUCT_BINDING: u32 = 1;
+    const TEXTURE_BINDING: u32 = 2;
+    const READ_TEXTURE_BINDING: u32 = 3;
+    const SAMPLER_BINDING: u32 = 4;
+    const FRAME_TEXTURE_BINDING: u32 = 5;
+
+    pub fn new(device: &wgpu::Device, processor: &ShaderCache, aovs: Aovs) -> Self {
+        let array_texture = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("AOV Accumulation Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::RAY_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::PER_DRAW_STRUCT_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                array_texture(Self::READ_TEXTURE_BINDING),
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::SAMPLER_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                array_texture(Self::FRAME_TEXTURE_BINDING),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("AOV Accumulation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut defines = FastHashMap::default();
+        aovs.insert_defines(&mut defines);
+        let module = processor
+            .compile_compute(
+                include_str!(concat!(
+                    "..",
+                    path_separator!(),
+                    "..",
+                    path_separator!(),
+                    "shaders",
+                    path_separator!(),
+                    "accumulation-aov.comp"
+                )),
+                Some(&defines),
+            )
+            .unwrap();
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("AOV Accumulation Shader"),
+                source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+            });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("AOV Accumulation Pipeline"),
+            layout: Some(&pipeline_layout),
+            entry_point: Some("main"),
+            module: &shader,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        AovAccumulationPass {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Create the bind group, all views are array views of textures
+    /// created with [`Aovs::create_texture`].
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        in_rays: gpu::StorageBufferSlice<Ray>,
+        global_uniforms: gpu::UniformBufferSlice<PerDrawUniforms>,
+        frame_aovs: &wgpu::TextureView,
+        write_view: &wgpu::TextureView,
+        input_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("AOV Accumulation Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::RAY_BINDING,
+                    resource: in_rays.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(write_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::PER_DRAW_STRUCT_BINDING,
+                    resource: global_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::READ_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::SAMPLER_BINDING,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::FRAME_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(frame_aovs),
+                },
+            ],
+        })
+    }
+
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame_bind_groups: &wgpu::BindGroup,
+        size: (u32, u32, u32),
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("AOV Accumulation Pass"),
+            timestamp_writes: None,
+        });
+        let workgroups = get_dispatch_size(&size, &Self::WORKGROUP_SIZE);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, frame_bind_groups, &[]);
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+}
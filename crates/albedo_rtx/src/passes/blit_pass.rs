@@ -5,6 +5,8 @@ use albedo_backend::gpu;
 use wgpu::{BindGroup, BindingType, StoreOp};
 
 use crate::macros::path_separator;
+use crate::passes::AccumulationTarget;
+use crate::tonemapping::TonemappingParameters;
 use crate::uniforms;
 
 pub struct BlitPass {
@@ -16,6 +18,9 @@ impl BlitPass {
     const TEXTURE_SAMPLER_BINDING: u32 = 0;
     const TEXTURE_BINDING: u32 = 1;
     const PER_DRAW_STRUCT_BINDING: u32 = 2;
+    const EXPOSURE_BINDING: u32 = 3;
+    const TONEMAPPING_BINDING: u32 = 4;
+    const ALPHA_BINDING: u32 = 5;
 
     pub fn new(
         device: &wgpu::Device,
@@ -53,6 +58,36 @@ impl BlitPass {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::EXPOSURE_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::TONEMAPPING_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::ALPHA_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -126,12 +161,21 @@ impl BlitPass {
         }
     }
 
+    /// Create the bind group.
+    ///
+    /// The radiance is scaled by `exposure` before tonemapping, see
+    /// [`uniforms::Exposure`], and then tonemapped according to
+    /// `tonemapping`.
+    ///
+    /// The output has the accumulated alpha, and its color is premultiplied.
     pub fn create_frame_bind_groups(
         &self,
         device: &wgpu::Device,
-        view: &wgpu::TextureView,
+        accumulated: AccumulationTarget,
         sampler: &wgpu::Sampler,
         global_uniforms: gpu::UniformBufferSlice<uniforms::PerDrawUniforms>,
+        exposure: gpu::StorageBufferSlice<uniforms::Exposure>,
+        tonemapping: gpu::UniformBufferSlice<TonemappingParameters>,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Blit Bind Group"),
@@ -143,12 +187,24 @@ impl BlitPass {
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::TEXTURE_BINDING,
-                    resource: wgpu::BindingResource::TextureView(view),
+                    resource: wgpu::BindingResource::TextureView(accumulated.radiance),
                 },
                 wgpu::BindGroupEntry {
                     binding: Self::PER_DRAW_STRUCT_BINDING,
                     resource: global_uniforms.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: Self::EXPOSURE_BINDING,
+                    resource: exposure.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::TONEMAPPING_BINDING,
+                    resource: tonemapping.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::ALPHA_BINDING,
+                    resource: wgpu::BindingResource::TextureView(accumulated.alpha),
+                },
             ],
         })
     }
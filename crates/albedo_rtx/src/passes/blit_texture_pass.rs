@@ -1,9 +1,12 @@
 use std::borrow::Cow;
 
 use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu;
 use wgpu::{BindGroup, BindingType, StoreOp};
 
 use crate::macros::path_separator;
+use crate::tonemapping::TonemappingParameters;
+use crate::uniforms;
 
 pub struct BlitTexturePass {
     bind_group_layout: wgpu::BindGroupLayout,
@@ -13,6 +16,8 @@ pub struct BlitTexturePass {
 impl BlitTexturePass {
     const TEXTURE_SAMPLER_BINDING: u32 = 0;
     const TEXTURE_BINDING: u32 = 1;
+    const TONEMAPPING_BINDING: u32 = 2;
+    const EXPOSURE_BINDING: u32 = 3;
 
     pub fn new(
         device: &wgpu::Device,
@@ -40,6 +45,26 @@ impl BlitTexturePass {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::TONEMAPPING_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::EXPOSURE_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -114,11 +139,17 @@ impl BlitTexturePass {
         }
     }
 
+    /// Create the bind group.
+    ///
+    /// Like the [`super::BlitPass`], the texture is scaled by `exposure`
+    /// before tonemapping, see [`uniforms::Exposure`].
     pub fn create_frame_bind_groups(
         &self,
         device: &wgpu::Device,
         view: &wgpu::TextureView,
         sampler: &wgpu::Sampler,
+        exposure: gpu::StorageBufferSlice<uniforms::Exposure>,
+        tonemapping: gpu::UniformBufferSlice<TonemappingParameters>,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("BlitTexture Bind Group"),
@@ -132,6 +163,14 @@ impl BlitTexturePass {
                     binding: Self::TEXTURE_BINDING,
                     resource: wgpu::BindingResource::TextureView(view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: Self::EXPOSURE_BINDING,
+                    resource: exposure.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::TONEMAPPING_BINDING,
+                    resource: tonemapping.as_entire_binding(),
+                },
             ],
         })
     }
@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+
+use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu::{self, ComputePipeline};
+
+use crate::get_dispatch_size;
+use crate::macros::path_separator;
+use crate::uniforms::BloomParameters;
+
+/// Bloom and glare, applied on the accumulated radiance before tonemapping.
+///
+/// The radiance above the threshold is downsampled in a chain of half
+/// resolution textures, upsampled back, and added to the radiance. The
+/// output keeps the sum of the weights in alpha, and can thus be bound as
+/// is to the [`super::BlitPass`].
+pub struct BloomPass {
+    frame_bind_group_layout: wgpu::BindGroupLayout,
+    layout: wgpu::PipelineLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+/// Bind groups of the [`BloomPass`], one per dispatch.
+///
+/// Holds the downsampling and upsampling chains, and must thus be
+/// re-created when the size changes.
+pub struct BloomBindGroups {
+    steps: Vec<BloomStep>,
+}
+
+struct BloomStep {
+    stage: u32,
+    level: u32,
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32, u32),
+}
+
+impl BloomPass {
+    const WORKGROUP_SIZE: (u32, u32, u32) = (8, 8, 1);
+
+    const SOURCE_BINDING: u32 = 0;
+    const PREVIOUS_BINDING: u32 = 1;
+    const TARGET_BINDING: u32 = 2;
+    const PARAMETERS_BINDING: u32 = 3;
+    const SAMPLER_BINDING: u32 = 4;
+
+    const STAGE_PREFILTER: u32 = 0;
+    const STAGE_DOWNSAMPLE: u32 = 1;
+    const STAGE_UPSAMPLE: u32 = 2;
+    const STAGE_COMPOSITE: u32 = 3;
+
+    /// Maximum number of levels of the downsampling chain.
+    pub const MAX_LEVELS: u32 = 6;
+
+    pub fn new(device: &wgpu::Device, processor: &ShaderCache) -> Self {
+        let texture = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let frame_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Bloom Bind Group Layout"),
+                entries: &[
+                    texture(Self::SOURCE_BINDING),
+                    texture(Self::PREVIOUS_BINDING),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: Self::TARGET_BINDING,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            format: wgpu::TextureFormat::Rgba32Float,
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: Self::PARAMETERS_BINDING,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: Self::SAMPLER_BINDING,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&frame_bind_group_layout],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::COMPUTE,
+                range: 0..8,
+            }],
+        });
+
+        let module = processor
+            .compile_compute(
+                include_str!(concat!(
+                    "..",
+                    path_separator!(),
+                    "..",
+                    path_separator!(),
+                    "shaders",
+                    path_separator!(),
+                    "bloom.comp"
+                )),
+                None,
+            )
+            .unwrap();
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Bloom Shader"),
+                source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+            });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Bloom Pipeline"),
+            layout: Some(&pipeline_layout),
+            entry_point: Some("main"),
+            module: &shader,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            frame_bind_group_layout,
+            layout: pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Create the downsampling chain, and the bind groups of each dispatch.
+    ///
+    /// `radiance` is the accumulated radiance, with the sum of the weights
+    /// in alpha, e.g., the output of the [`super::AccumulationPass`].
+    /// `out_radiance` must be a different texture of the same size, at
+    /// least four pixels wide.
+    ///
+    /// Texels are fetched unfiltered, `sampler` is bound like for the
+    /// [`super::ATrousPass`].
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        out_radiance: &wgpu::TextureView,
+        radiance: &wgpu::TextureView,
+        parameters: gpu::UniformBufferSlice<BloomParameters>,
+        sampler: &wgpu::Sampler,
+        size: (u32, u32),
+    ) -> BloomBindGroups {
+        // The chain stops once the levels are a few pixels wide, but keeps
+        // two levels to upsample from.
+        let levels = size
+            .0
+            .min(size.1)
+            .max(1)
+            .ilog2()
+            .saturating_sub(2)
+            .clamp(2, Self::MAX_LEVELS);
+        let extent = wgpu::Extent3d {
+            width: (size.0 / 2).max(1),
+            height: (size.1 / 2).max(1),
+            depth_or_array_layers: 1,
+        };
+        let create_chain = |label: &str, mip_level_count: u32| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: extent,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+        let level_views = |texture: &wgpu::Texture| -> Vec<wgpu::TextureView> {
+            (0..texture.mip_level_count())
+                .map(|level| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        base_mip_level: level,
+                        mip_level_count: Some(1),
+                        ..Default::default()
+                    })
+                })
+                .collect()
+        };
+        let down = level_views(&create_chain("Bloom Downsampling Chain", levels));
+        let up = level_views(&create_chain("Bloom Upsampling Chain", levels - 1));
+
+        let level_size = |level: u32| {
+            (
+                (extent.width >> level).max(1),
+                (extent.height >> level).max(1),
+                1,
+            )
+        };
+        let mut steps = Vec::with_capacity(2 * levels as usize);
+        let mut add_step = |stage: u32,
+                            level: u32,
+                            source: &wgpu::TextureView,
+                            previous: &wgpu::TextureView,
+                            target: &wgpu::TextureView,
+                            size: (u32, u32, u32)| {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bloom Frame Bind Group"),
+                layout: &self.frame_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: Self::SOURCE_BINDING,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: Self::PREVIOUS_BINDING,
+                        resource: wgpu::BindingResource::TextureView(previous),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: Self::TARGET_BINDING,
+                        resource: wgpu::BindingResource::TextureView(target),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: Self::PARAMETERS_BINDING,
+                        resource: parameters.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: Self::SAMPLER_BINDING,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            });
+            steps.push(BloomStep {
+                stage,
+                level,
+                bind_group,
+                size,
+            });
+        };
+
+        // Downsampling stages don't read `previous`, the source is bound instead.
+        add_step(
+            Self::STAGE_PREFILTER,
+            0,
+            radiance,
+            radiance,
+            &down[0],
+            level_size(0),
+        );
+        for level in 1..levels {
+            let source = &down[level as usize - 1];
+            add_step(
+                Self::STAGE_DOWNSAMPLE,
+                level,
+                source,
+                source,
+                &down[level as usize],
+                level_size(level),
+            );
+        }
+        for level in (0..levels - 1).rev() {
+            let coarser = if level == levels - 2 {
+                &down[level as usize + 1]
+            } else {
+                &up[level as usize + 1]
+            };
+            add_step(
+                Self::STAGE_UPSAMPLE,
+                level,
+                &down[level as usize],
+                coarser,
+                &up[level as usize],
+                level_size(level),
+            );
+        }
+        add_step(
+            Self::STAGE_COMPOSITE,
+            0,
+            radiance,
+            &up[0],
+            out_radiance,
+            (size.0, size.1, 1),
+        );
+
+        BloomBindGroups { steps }
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, bind_groups: &BloomBindGroups) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Bloom Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        for step in &bind_groups.steps {
+            let workgroups = get_dispatch_size(&step.size, &Self::WORKGROUP_SIZE);
+            pass.set_bind_group(0, &step.bind_group, &[]);
+            pass.set_push_constants(0, bytemuck::cast_slice(&[step.stage, step.level]));
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+        }
+    }
+}
+
+impl ComputePipeline for BloomPass {
+    const LABEL: &'static str = "Bloom Pipeline";
+    const SHADER_ID: &'static str = "bloom.comp";
+
+    fn get_pipeline_layout(&self) -> &wgpu::PipelineLayout {
+        &self.layout
+    }
+
+    fn set_pipeline(&mut self, pipeline: wgpu::ComputePipeline) {
+        self.pipeline = pipeline;
+    }
+}
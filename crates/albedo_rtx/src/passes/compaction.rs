@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+
+use albedo_backend::gpu;
+
+use crate::get_dispatch_size;
+use crate::macros::path_separator;
+use crate::uniforms;
+
+/// Ray compaction pass.
+///
+/// Writes the index of every ray that isn't terminated into a list of
+/// active rays, and counts them.
+///
+/// The list starts with a header of 4 `u32`, laid out as
+/// [`wgpu::util::DispatchIndirectArgs`] followed by the active ray count.
+/// [`super::IntersectorPass::dispatch_indirect`] and
+/// [`super::ShadingPass::dispatch_indirect`] use it to only run one
+/// invocation per surviving path.
+///
+/// Compaction is meant for secondary bounces: the primary bounce has no
+/// terminated rays, and the gbuffer isn't supported with compacted shading.
+pub struct RayCompactionPass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl RayCompactionPass {
+    const RAY_BINDING: u32 = 0;
+    const ACTIVE_RAYS_BINDING: u32 = 1;
+
+    const WORKGROUP_SIZE: (u32, u32, u32) = (8, 8, 1);
+
+    /// Number of `u32` in the header of the active ray list.
+    pub const HEADER_SIZE: u64 = 4;
+
+    pub fn new(device: &wgpu::Device, source: Option<&str>) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ray Compaction Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::RAY_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::ACTIVE_RAYS_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ray Compaction Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // The GLSL frontend doesn't support atomics, this shader is thus
+        // written in WGSL.
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Ray Compaction Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source.unwrap_or(include_str!(
+                    concat!(
+                        "..",
+                        path_separator!(),
+                        "..",
+                        path_separator!(),
+                        "shaders",
+                        path_separator!(),
+                        "ray_compaction.wgsl"
+                    )
+                )))),
+            });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Ray Compaction Pipeline"),
+            layout: Some(&pipeline_layout),
+            entry_point: Some("main"),
+            module: &shader,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Create a list of active rays large enough for `ray_count` rays.
+    pub fn create_active_rays(device: &wgpu::Device, ray_count: u64) -> gpu::Buffer<u32> {
+        gpu::Buffer::new_storage(
+            device,
+            Self::HEADER_SIZE + ray_count,
+            Some(gpu::BufferInitDescriptor::new(
+                Some("Active Rays Buffer"),
+                wgpu::BufferUsages::INDIRECT,
+            )),
+        )
+    }
+
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        rays: gpu::StorageBufferSlice<uniforms::Ray>,
+        out_active_rays: gpu::StorageBufferSlice<u32>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ray Compaction Frame Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::RAY_BINDING,
+                    resource: rays.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::ACTIVE_RAYS_BINDING,
+                    resource: out_active_rays.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Reset the header of `active_rays`, and compact the rays.
+    ///
+    /// `dispatch_size` must be the one used to generate the rays.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame_bind_groups: &wgpu::BindGroup,
+        active_rays: &gpu::Buffer<u32>,
+        dispatch_size: (u32, u32, u32),
+    ) {
+        encoder.clear_buffer(
+            active_rays.inner(),
+            0,
+            Some(Self::HEADER_SIZE * std::mem::size_of::<u32>() as u64),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Ray Compaction Pass"),
+            timestamp_writes: None,
+        });
+        let workgroups = get_dispatch_size(&dispatch_size, &Self::WORKGROUP_SIZE);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, frame_bind_groups, &[]);
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+}
@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+
+use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu;
+use wgpu::naga::FastHashMap;
+
+use super::Aovs;
+use crate::get_dispatch_size;
+use crate::macros::path_separator;
+use crate::uniforms::PerDrawUniforms;
+
+/// Cryptomatte accumulation pass.
+///
+/// Accumulates the coverage of the instances, or materials, per pixel.
+/// Identifiers are read from the frame AOVs written by the [`super::ShadingPass`],
+/// and mapped to the hashes of their names, see [`crate::cryptomatte`].
+///
+/// Each layer of the output stores two ranks, as `(hash, coverage, hash, coverage)`,
+/// sorted by decreasing coverage. Layers can thus be written as is to the
+/// `CryptoObject00`, `CryptoObject01`, ... EXR layers.
+///
+/// The coverage is the fraction of samples hitting an identifier, the pixel
+/// filter must thus be importance sampled, e.g., not [`crate::Filter::Mitchell`].
+/// Samples must also be jittered, i.e., [`crate::Camera::filter_radius`] must
+/// be positive, see [`crate::Camera::set_filter`]. Otherwise every sample of a
+/// pixel hits the same identifier, and mattes aren't anti-aliased.
+pub struct CryptomattePass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl CryptomattePass {
+    const WORKGROUP_SIZE: (u32, u32, u32) = (8, 8, 1);
+
+    /// Maximum number of ranks stored per pixel.
+    pub const MAX_RANKS: u32 = 16;
+
+    const HASH_BINDING: u32 = 0;
+    const PER_DRAW_STRUCT_BINDING: u32 = 1;
+    const TEXTURE_BINDING: u32 = 2;
+    const READ_TEXTURE_BINDING: u32 = 3;
+    const SAMPLER_BINDING: u32 = 4;
+    const FRAME_TEXTURE_BINDING: u32 = 5;
+
+    /// Create the pass, matting the identifier `id` of the frame AOVs.
+    ///
+    /// `id` is either [`Aovs::INSTANCE_ID`] or [`Aovs::MATERIAL_ID`], and must
+    /// be part of `aovs`.
+    pub fn new(device: &wgpu::Device, processor: &ShaderCache, aovs: Aovs, id: Aovs) -> Self {
+        assert!(
+            id == Aovs::INSTANCE_ID || id == Aovs::MATERIAL_ID,
+            "Cryptomatte requires an identifier AOV"
+        );
+        let Some(layer) = aovs.layer(id) else {
+            panic!("Cryptomatte identifier {:?} isn't part of the AOVs", id)
+        };
+
+        let array_texture = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cryptomatte Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::HASH_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::PER_DRAW_STRUCT_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+                array_texture(Self::READ_TEXTURE_BINDING),
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::SAMPLER_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                array_texture(Self::FRAME_TEXTURE_BINDING),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cryptomatte Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut defines: FastHashMap<String, String> = FastHashMap::default();
+        defines.insert("ID_LAYER".into(), layer.to_string());
+        let module = processor
+            .compile_compute(
+                include_str!(concat!(
+                    "..",
+                    path_separator!(),
+                    "..",
+                    path_separator!(),
+                    "shaders",
+                    path_separator!(),
+                    "cryptomatte.comp"
+                )),
+                Some(&defines),
+            )
+            .unwrap();
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Cryptomatte Shader"),
+                source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+            });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Cryptomatte Pipeline"),
+            layout: Some(&pipeline_layout),
+            entry_point: Some("main"),
+            module: &shader,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        CryptomattePass {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Create the accumulation texture, storing `ranks` ranks per pixel.
+    ///
+    /// Cryptomatte usually stores `6` ranks.
+    pub fn create_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        ranks: u32,
+    ) -> wgpu::Texture {
+        let ranks = ranks.clamp(2, Self::MAX_RANKS);
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cryptomatte Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: ranks.div_ceil(2),
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Create the bind group.
+    ///
+    /// `hashes` maps each identifier to the hash of its name, see
+    /// [`crate::cryptomatte::CryptomatteManifest::from_names`].
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        hashes: gpu::StorageBufferSlice<u32>,
+        global_uniforms: gpu::UniformBufferSlice<PerDrawUniforms>,
+        frame_aovs: &wgpu::TextureView,
+        write_view: &wgpu::TextureView,
+        input_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cryptomatte Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::HASH_BINDING,
+                    resource: hashes.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::PER_DRAW_STRUCT_BINDING,
+                    resource: global_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(write_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::READ_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::SAMPLER_BINDING,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::FRAME_TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(frame_aovs),
+                },
+            ],
+        })
+    }
+
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame_bind_groups: &wgpu::BindGroup,
+        size: (u32, u32, u32),
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Cryptomatte Pass"),
+            timestamp_writes: None,
+        });
+        let workgroups = get_dispatch_size(&size, &Self::WORKGROUP_SIZE);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, frame_bind_groups, &[]);
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+}
@@ -8,6 +8,7 @@ use crate::macros::path_separator;
 use super::super::GBUFFER_READ_TY;
 
 pub struct CompositingPass {
+    frame_bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::ComputePipeline,
 }
 
@@ -113,7 +114,42 @@ impl CompositingPass {
             cache: None,
         });
 
-        Ok(Self { pipeline })
+        Ok(Self {
+            frame_bind_group_layout,
+            pipeline,
+        })
+    }
+
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        out_radiance: &wgpu::TextureView,
+        gbuffer: &wgpu::TextureView,
+        radiance: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compositing Frame Bind Group"),
+            layout: &self.frame_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::GBUFFER_BINDING,
+                    resource: wgpu::BindingResource::TextureView(gbuffer),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::RADIANCE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(radiance),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::RADIANCE_OUT_BINDING,
+                    resource: wgpu::BindingResource::TextureView(out_radiance),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::SAMPLER_BINDING,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
     }
 
     pub fn dispatch(
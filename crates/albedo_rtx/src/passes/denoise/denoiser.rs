@@ -0,0 +1,309 @@
+use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu;
+
+use crate::get_dispatch_size;
+use crate::passes::{ATrousPass, TemporalAccumulationPass};
+use crate::uniforms::{DenoiseResources, Ray};
+
+use super::CompositingPass;
+
+/// Spatiotemporal variance-guided filtering (SVGF) of the path traced
+/// radiance.
+///
+/// Runs the [`TemporalAccumulationPass`], the iterations of the
+/// [`ATrousPass`], and the [`CompositingPass`], and owns the textures and
+/// history they share.
+///
+/// The shading must emit the gbuffer of each frame, see [`Self::resources`].
+/// The denoised radiance is written to [`Self::output`], which can be
+/// displayed using the [`crate::passes::BlitTexturePass`].
+pub struct Denoiser {
+    temporal: TemporalAccumulationPass,
+    atrous: ATrousPass,
+    compositing: CompositingPass,
+    sampler: wgpu::Sampler,
+    targets: DenoiserTargets,
+}
+
+/// Textures and bind groups, indexed by the parity of the frame.
+struct DenoiserTargets {
+    size: (u32, u32),
+    gbuffers: [wgpu::TextureView; 2],
+    motion: wgpu::TextureView,
+    /// Ping-pong textures of the à-trous iterations, the first one holding
+    /// the output of the temporal accumulation.
+    radiance: [wgpu::Texture; 2],
+    /// Output of the first à-trous iteration, accumulated on the next frame.
+    history: wgpu::Texture,
+    output: wgpu::TextureView,
+    temporal_bind_groups: [wgpu::BindGroup; 2],
+    atrous_bind_groups: [[wgpu::BindGroup; 2]; 2],
+    /// Also indexed by the radiance texture holding the last iteration.
+    compositing_bind_groups: [[wgpu::BindGroup; 2]; 2],
+}
+
+impl Denoiser {
+    /// Number of à-trous iterations used by default.
+    pub const DEFAULT_ITERATIONS: u8 = 4;
+
+    /// Create the denoiser, and its textures for a frame of `size`.
+    ///
+    /// `rays` holds the radiance to denoise, laid out by the
+    /// [`crate::passes::RayPass`].
+    pub fn new(
+        device: &wgpu::Device,
+        processor: &ShaderCache,
+        size: (u32, u32),
+        rays: &gpu::Buffer<Ray>,
+    ) -> Self {
+        let temporal = TemporalAccumulationPass::new_inlined(device, processor);
+        let mut atrous = ATrousPass::new(device, processor);
+        atrous.set_count(Self::DEFAULT_ITERATIONS);
+        let compositing = CompositingPass::new_inlined(device, processor);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Denoiser Sampler"),
+            ..Default::default()
+        });
+        let targets = DenoiserTargets::new(
+            device,
+            &temporal,
+            &atrous,
+            &compositing,
+            &sampler,
+            size,
+            rays,
+        );
+        Self {
+            temporal,
+            atrous,
+            compositing,
+            sampler,
+            targets,
+        }
+    }
+
+    /// Re-create the textures for a frame of `size`, discarding the history.
+    ///
+    /// Must also be called when the `rays` buffer is re-created.
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32), rays: &gpu::Buffer<Ray>) {
+        self.targets = DenoiserTargets::new(
+            device,
+            &self.temporal,
+            &self.atrous,
+            &self.compositing,
+            &self.sampler,
+            size,
+            rays,
+        );
+    }
+
+    /// Number of à-trous iterations, with the step size doubling at each one.
+    pub fn iterations(&self) -> u8 {
+        self.atrous.count()
+    }
+
+    /// Set the number of à-trous iterations, clamped to
+    /// `[1; ATrousPass::MAX_COUNT]`, see [`ATrousPass::set_count`].
+    pub fn set_iterations(&mut self, iterations: u8) {
+        self.atrous.set_count(iterations);
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.targets.size
+    }
+
+    /// Gbuffer and motion vectors the shading writes to on `frame`.
+    ///
+    /// Gbuffers alternate between frames, the bind groups of the shading
+    /// must thus follow the parity of `frame`.
+    pub fn resources(&self, frame: u32) -> DenoiseResources<'_> {
+        let current = (frame % 2) as usize;
+        DenoiseResources {
+            gbuffer_current: &self.targets.gbuffers[current],
+            gbuffer_previous: &self.targets.gbuffers[1 - current],
+            motion: &self.targets.motion,
+        }
+    }
+
+    /// Denoised radiance, with the variance in alpha.
+    pub fn output(&self) -> &wgpu::TextureView {
+        &self.targets.output
+    }
+
+    /// Denoise the radiance of `frame`, once shaded.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, frame: u32) {
+        let targets = &self.targets;
+        let current = (frame % 2) as usize;
+        let size = (targets.size.0, targets.size.1, 1);
+
+        self.temporal
+            .dispatch(encoder, &targets.temporal_bind_groups[current], &size);
+        self.atrous.dispatch(
+            encoder,
+            &targets.atrous_bind_groups[current],
+            &targets.radiance[1],
+            &targets.history,
+            &size,
+        );
+        // Iterations alternate from the first radiance texture to the second.
+        let last = (self.atrous.count() % 2) as usize;
+        self.compositing.dispatch(
+            encoder,
+            &targets.compositing_bind_groups[current][last],
+            &size,
+        );
+    }
+}
+
+impl DenoiserTargets {
+    fn new(
+        device: &wgpu::Device,
+        temporal: &TemporalAccumulationPass,
+        atrous: &ATrousPass,
+        compositing: &CompositingPass,
+        sampler: &wgpu::Sampler,
+        size: (u32, u32),
+        rays: &gpu::Buffer<Ray>,
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size.0.max(1),
+            height: size.1.max(1),
+            depth_or_array_layers: 1,
+        };
+        let create_texture =
+            |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+                device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: usage | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+            };
+        let storage = wgpu::TextureUsages::STORAGE_BINDING;
+        let view = |texture: &wgpu::Texture| texture.create_view(&Default::default());
+
+        let gbuffers = [
+            view(&create_texture(
+                "Denoiser Gbuffer 0",
+                wgpu::TextureFormat::Rgba32Uint,
+                storage,
+            )),
+            view(&create_texture(
+                "Denoiser Gbuffer 1",
+                wgpu::TextureFormat::Rgba32Uint,
+                storage,
+            )),
+        ];
+        let motion = view(&create_texture(
+            "Denoiser Motion",
+            wgpu::TextureFormat::Rg32Float,
+            storage,
+        ));
+        let radiance = [
+            create_texture(
+                "Denoiser Radiance 0",
+                wgpu::TextureFormat::Rgba32Float,
+                storage,
+            ),
+            create_texture(
+                "Denoiser Radiance 1",
+                wgpu::TextureFormat::Rgba32Float,
+                storage | wgpu::TextureUsages::COPY_SRC,
+            ),
+        ];
+        let radiance_views = [view(&radiance[0]), view(&radiance[1])];
+        let history = create_texture(
+            "Denoiser Radiance History",
+            wgpu::TextureFormat::Rgba32Float,
+            wgpu::TextureUsages::COPY_DST,
+        );
+        let history_view = view(&history);
+        let moments = [
+            view(&create_texture(
+                "Denoiser Moments 0",
+                wgpu::TextureFormat::Rg32Float,
+                storage,
+            )),
+            view(&create_texture(
+                "Denoiser Moments 1",
+                wgpu::TextureFormat::Rg32Float,
+                storage,
+            )),
+        ];
+        let output = view(&create_texture(
+            "Denoiser Output",
+            wgpu::TextureFormat::Rgba32Float,
+            storage,
+        ));
+
+        // History lengths are indexed like the rays, i.e., on the grid of
+        // the workgroups.
+        let workgroups = get_dispatch_size(&(extent.width, extent.height, 1), &(8, 8, 1));
+        let history_count = (workgroups.0 * 8) as u64 * (workgroups.1 * 8) as u64;
+        let create_history = |label: &str| {
+            gpu::Buffer::<u32>::new_storage(
+                device,
+                history_count,
+                Some(gpu::BufferInitDescriptor::with_label(Some(label))),
+            )
+        };
+        let history_lengths = [
+            create_history("Denoiser History Length 0"),
+            create_history("Denoiser History Length 1"),
+        ];
+
+        let temporal_bind_groups = [0, 1].map(|current| {
+            let previous = 1 - current;
+            temporal.create_frame_bind_groups(
+                device,
+                &radiance_views[0],
+                &moments[current],
+                &history_lengths[current],
+                rays,
+                &gbuffers[previous],
+                &gbuffers[current],
+                &motion,
+                &history_view,
+                sampler,
+                &history_lengths[previous],
+                &moments[previous],
+            )
+        });
+        let atrous_bind_groups = [0, 1].map(|current| {
+            atrous.create_frame_bind_groups(
+                device,
+                &radiance_views[1],
+                &gbuffers[current],
+                &radiance_views[0],
+                sampler,
+            )
+        });
+        let compositing_bind_groups = [0, 1].map(|current| {
+            [0, 1].map(|last| {
+                compositing.create_frame_bind_groups(
+                    device,
+                    &output,
+                    &gbuffers[current],
+                    &radiance_views[last],
+                    sampler,
+                )
+            })
+        });
+
+        Self {
+            size: (extent.width, extent.height),
+            gbuffers,
+            motion,
+            radiance,
+            history,
+            output,
+            temporal_bind_groups,
+            atrous_bind_groups,
+            compositing_bind_groups,
+        }
+    }
+}
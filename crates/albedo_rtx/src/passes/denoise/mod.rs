@@ -1,3 +1,5 @@
 mod composite;
+mod denoiser;
 
 pub use composite::*;
+pub use denoiser::*;
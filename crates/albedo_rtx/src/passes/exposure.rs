@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use albedo_backend::gpu;
+
+use crate::get_dispatch_size;
+use crate::macros::path_separator;
+use crate::uniforms::{AutoExposureParameters, Exposure, PerDrawUniforms};
+
+/// Automatic exposure pass.
+///
+/// Builds a histogram of the log luminance of the accumulated image, and
+/// writes the [`Exposure`] mapping its average luminance to middle grey.
+/// The exposure buffer can then be bound as is to the [`super::BlitPass`], or
+/// the [`super::BlitTexturePass`].
+///
+/// The exposure adapts over frames, see [`AutoExposureParameters::adaptation`].
+pub struct AutoExposurePass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    histogram: gpu::Buffer<u32>,
+}
+
+impl AutoExposurePass {
+    const TEXTURE_BINDING: u32 = 0;
+    const PER_DRAW_STRUCT_BINDING: u32 = 1;
+    const PARAMETERS_BINDING: u32 = 2;
+    const HISTOGRAM_BINDING: u32 = 3;
+    const EXPOSURE_BINDING: u32 = 4;
+
+    const WORKGROUP_SIZE: (u32, u32, u32) = (16, 16, 1);
+
+    /// Number of bins of the luminance histogram.
+    pub const BIN_COUNT: u64 = 256;
+
+    pub fn new(device: &wgpu::Device, source: Option<&str>) -> Self {
+        let uniform = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Auto Exposure Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                uniform(Self::PER_DRAW_STRUCT_BINDING),
+                uniform(Self::PARAMETERS_BINDING),
+                storage(Self::HISTOGRAM_BINDING),
+                storage(Self::EXPOSURE_BINDING),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Auto Exposure Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // The GLSL frontend doesn't support atomics, this shader is thus
+        // written in WGSL.
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Auto Exposure Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source.unwrap_or(include_str!(
+                    concat!(
+                        "..",
+                        path_separator!(),
+                        "..",
+                        path_separator!(),
+                        "shaders",
+                        path_separator!(),
+                        "auto_exposure.wgsl"
+                    )
+                )))),
+            });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                entry_point: Some(entry_point),
+                module: &shader,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let histogram_pipeline = create_pipeline("Auto Exposure Histogram Pipeline", "histogram");
+        let average_pipeline = create_pipeline("Auto Exposure Average Pipeline", "average");
+
+        let histogram = gpu::Buffer::new_storage(
+            device,
+            Self::BIN_COUNT,
+            Some(gpu::BufferInitDescriptor::new(
+                Some("Auto Exposure Histogram"),
+                wgpu::BufferUsages::COPY_DST,
+            )),
+        );
+
+        Self {
+            bind_group_layout,
+            histogram_pipeline,
+            average_pipeline,
+            histogram,
+        }
+    }
+
+    /// Create the bind group.
+    ///
+    /// `accumulated` is the accumulated radiance, with the sum of the
+    /// weights in alpha, e.g., the output of the [`super::AccumulationPass`].
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        accumulated: &wgpu::TextureView,
+        global_uniforms: gpu::UniformBufferSlice<PerDrawUniforms>,
+        parameters: gpu::UniformBufferSlice<AutoExposureParameters>,
+        exposure: gpu::StorageBufferSlice<Exposure>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Auto Exposure Frame Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::TEXTURE_BINDING,
+                    resource: wgpu::BindingResource::TextureView(accumulated),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::PER_DRAW_STRUCT_BINDING,
+                    resource: global_uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::PARAMETERS_BINDING,
+                    resource: parameters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::HISTOGRAM_BINDING,
+                    resource: self.histogram.inner().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::EXPOSURE_BINDING,
+                    resource: exposure.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Reset the histogram, and compute the exposure.
+    ///
+    /// `size` is the size of the rendered image.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame_bind_groups: &wgpu::BindGroup,
+        size: (u32, u32, u32),
+    ) {
+        encoder.clear_buffer(self.histogram.inner(), 0, None);
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Auto Exposure Pass"),
+            timestamp_writes: None,
+        });
+        let workgroups = get_dispatch_size(&size, &Self::WORKGROUP_SIZE);
+        pass.set_bind_group(0, frame_bind_groups, &[]);
+        pass.set_pipeline(&self.histogram_pipeline);
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+        pass.set_pipeline(&self.average_pipeline);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+}
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 
 use albedo_backend::{data::ShaderCache, gpu};
+use wgpu::naga::FastHashMap;
 use wgpu::ShaderModuleDescriptor;
 
 use crate::macros::path_separator;
@@ -14,6 +15,7 @@ pub struct IntersectorPass {
 impl IntersectorPass {
     const RAY_BINDING: u32 = 0;
     const INTERSECTION_BINDING: u32 = 1;
+    const ACTIVE_RAYS_BINDING: u32 = 2;
 
     pub fn new(
         device: &wgpu::Device,
@@ -21,31 +23,84 @@ impl IntersectorPass {
         geometry_layout: &crate::RTGeometryBindGroupLayout,
         source: Option<&str>,
     ) -> Self {
+        Self::new_with_defines(
+            device,
+            processor,
+            geometry_layout,
+            source,
+            &FastHashMap::default(),
+        )
+    }
+
+    /// Create an intersector running on the active rays written by
+    /// [`super::RayCompactionPass`].
+    ///
+    /// Intersections are written in the order of the active ray list,
+    /// and must be shaded by a [`super::ShadingPass`] created with the
+    /// `RAY_COMPACTION` define.
+    pub fn new_compacted(
+        device: &wgpu::Device,
+        processor: &ShaderCache,
+        geometry_layout: &crate::RTGeometryBindGroupLayout,
+        source: Option<&str>,
+    ) -> Self {
+        let mut defines: FastHashMap<String, String> = FastHashMap::default();
+        defines.insert("RAY_COMPACTION".into(), "".into());
+        Self::new_with_defines(device, processor, geometry_layout, source, &defines)
+    }
+
+    /// Create an intersector from the defines of `intersection.comp`.
+    ///
+    /// - `RAY_COMPACTION`: see [`IntersectorPass::new_compacted`]
+    /// - `AREA_LIGHTS`: the scene lights are intersected, and thus visible
+    ///   and emitting. Each ray then tests every light, and the
+    ///   [`super::ShadingPass`] must use the same define to shade the hits.
+    pub fn new_with_defines(
+        device: &wgpu::Device,
+        processor: &ShaderCache,
+        geometry_layout: &crate::RTGeometryBindGroupLayout,
+        source: Option<&str>,
+        defines: &FastHashMap<String, String>,
+    ) -> Self {
+        let compacted = defines.contains_key("RAY_COMPACTION");
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: Self::RAY_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: Self::INTERSECTION_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        if compacted {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::ACTIVE_RAYS_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        }
         let frame_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Intersector Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: Self::RAY_BINDING,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: Self::INTERSECTION_BINDING,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
+                entries: &entries,
             });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -65,7 +120,7 @@ impl IntersectorPass {
                     path_separator!(),
                     "intersection.comp"
                 ))),
-                None,
+                Some(defines),
             )
             .unwrap();
 
@@ -111,6 +166,35 @@ impl IntersectorPass {
         })
     }
 
+    /// Create the frame bind group of an intersector created with
+    /// [`IntersectorPass::new_compacted`].
+    pub fn create_compacted_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        out_intersections: gpu::StorageBufferSlice<uniforms::Intersection>,
+        rays: gpu::StorageBufferSlice<uniforms::Ray>,
+        active_rays: gpu::StorageBufferSlice<u32>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Intersector Compacted Frame Bind Group"),
+            layout: &self.frame_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::RAY_BINDING,
+                    resource: rays.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::INTERSECTION_BINDING,
+                    resource: out_intersections.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::ACTIVE_RAYS_BINDING,
+                    resource: active_rays.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
     pub fn dispatch(
         &self,
         encoder: &mut wgpu::CommandEncoder,
@@ -127,4 +211,23 @@ impl IntersectorPass {
         pass.set_bind_group(1, frame_bind_group, &[]);
         pass.dispatch_workgroups(dispatch_size.0, dispatch_size.1, dispatch_size.2);
     }
+
+    /// Dispatch one invocation per active ray, using the header written by
+    /// [`super::RayCompactionPass`].
+    pub fn dispatch_indirect(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_bind_group: &wgpu::BindGroup,
+        frame_bind_group: &wgpu::BindGroup,
+        active_rays: &gpu::Buffer<u32>,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Intersector Indirect Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, scene_bind_group, &[]);
+        pass.set_bind_group(1, frame_bind_group, &[]);
+        pass.dispatch_workgroups_indirect(active_rays.inner(), 0);
+    }
 }
@@ -121,7 +121,7 @@ impl LightmapPass {
 
         let layout_builder = gpu::VertexBufferLayoutBuilder::new(2)
             .auto_attribute(wgpu::VertexFormat::Float32x4)
-            .auto_attribute(wgpu::VertexFormat::Float32x4);
+            .auto_attribute(wgpu::VertexFormat::Uint32x4);
         let layout = layout_builder.build(None);
 
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
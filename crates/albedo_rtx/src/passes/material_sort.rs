@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+
+use albedo_backend::gpu;
+
+use crate::macros::path_separator;
+use crate::uniforms;
+
+/// Material sort pass.
+///
+/// Sorts the intersections written by [`super::IntersectorPass::new_compacted`]
+/// by material, with all misses last. Neighbouring shading invocations then
+/// mostly run the same material, and fetch the same textures.
+///
+/// Sorted intersections are written to a second buffer, and keep the index
+/// of their ray. They can thus directly be bound to a [`super::ShadingPass`]
+/// created with the `RAY_COMPACTION` define, in place of the unsorted ones.
+///
+/// Sorting can be toggled with [`MaterialSortPass::set_enabled`], e.g., to
+/// compare timings with [`gpu::Queries`]. Once disabled, intersections are
+/// copied in their original order, and the bind groups are left untouched.
+pub struct MaterialSortPass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    histogram_pipeline: wgpu::ComputePipeline,
+    scan_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    copy_pipeline: wgpu::ComputePipeline,
+    enabled: bool,
+}
+
+impl MaterialSortPass {
+    const INTERSECTION_BINDING: u32 = 0;
+    const ACTIVE_RAYS_BINDING: u32 = 1;
+    const BINS_BINDING: u32 = 2;
+    const SORTED_INTERSECTION_BINDING: u32 = 3;
+
+    pub fn new(device: &wgpu::Device, source: Option<&str>) -> Self {
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Sort Layout"),
+            entries: &[
+                storage(Self::INTERSECTION_BINDING, true),
+                storage(Self::ACTIVE_RAYS_BINDING, true),
+                storage(Self::BINS_BINDING, false),
+                storage(Self::SORTED_INTERSECTION_BINDING, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Material Sort Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // The GLSL frontend doesn't support atomics, this shader is thus
+        // written in WGSL.
+        let shader: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Material Sort Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source.unwrap_or(include_str!(
+                    concat!(
+                        "..",
+                        path_separator!(),
+                        "..",
+                        path_separator!(),
+                        "shaders",
+                        path_separator!(),
+                        "material_sort.wgsl"
+                    )
+                )))),
+            });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                entry_point: Some(entry_point),
+                module: &shader,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            bind_group_layout,
+            histogram_pipeline: create_pipeline("Material Sort Histogram Pipeline", "histogram"),
+            scan_pipeline: create_pipeline("Material Sort Scan Pipeline", "scan"),
+            scatter_pipeline: create_pipeline("Material Sort Scatter Pipeline", "scatter"),
+            copy_pipeline: create_pipeline("Material Sort Copy Pipeline", "copy"),
+            enabled: true,
+        }
+    }
+
+    /// `true` if intersections are sorted, the default.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Create the sorting bins, one per material and one for misses.
+    pub fn create_bins(device: &wgpu::Device, material_count: u64) -> gpu::Buffer<u32> {
+        gpu::Buffer::new_storage(
+            device,
+            material_count + 1,
+            Some(gpu::BufferInitDescriptor::with_label(Some(
+                "Material Sort Bins Buffer",
+            ))),
+        )
+    }
+
+    pub fn create_frame_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        intersections: gpu::StorageBufferSlice<uniforms::Intersection>,
+        active_rays: gpu::StorageBufferSlice<u32>,
+        bins: gpu::StorageBufferSlice<u32>,
+        out_intersections: gpu::StorageBufferSlice<uniforms::Intersection>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Sort Frame Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: Self::INTERSECTION_BINDING,
+                    resource: intersections.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::ACTIVE_RAYS_BINDING,
+                    resource: active_rays.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::BINS_BINDING,
+                    resource: bins.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: Self::SORTED_INTERSECTION_BINDING,
+                    resource: out_intersections.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Reset the bins, and sort the intersections of the active rays.
+    ///
+    /// When disabled, the intersections are only copied.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame_bind_groups: &wgpu::BindGroup,
+        bins: &gpu::Buffer<u32>,
+        active_rays: &gpu::Buffer<u32>,
+    ) {
+        if !self.enabled {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Material Sort Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, frame_bind_groups, &[]);
+            pass.set_pipeline(&self.copy_pipeline);
+            pass.dispatch_workgroups_indirect(active_rays.inner(), 0);
+            return;
+        }
+
+        encoder.clear_buffer(bins.inner(), 0, None);
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Material Sort Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_bind_group(0, frame_bind_groups, &[]);
+
+        pass.set_pipeline(&self.histogram_pipeline);
+        pass.dispatch_workgroups_indirect(active_rays.inner(), 0);
+
+        pass.set_pipeline(&self.scan_pipeline);
+        pass.dispatch_workgroups(1, 1, 1);
+
+        pass.set_pipeline(&self.scatter_pipeline);
+        pass.dispatch_workgroups_indirect(active_rays.inner(), 0);
+    }
+}
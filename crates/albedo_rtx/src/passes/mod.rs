@@ -1,23 +1,35 @@
 mod a_trous;
 mod accumulation;
+mod aov;
 mod blit_pass;
 mod blit_texture_pass;
+mod bloom;
+mod compaction;
+mod cryptomatte;
 mod denoise;
+mod exposure;
 mod intersector;
 mod lightmap;
+mod material_sort;
 mod ray;
 mod shading;
 mod temporal_accumulation;
 
 pub use a_trous::ATrousPass;
-pub use accumulation::AccumulationPass;
+pub use accumulation::{AccumulationPass, AccumulationTarget};
+pub use aov::{AovAccumulationPass, Aovs};
 pub use blit_pass::BlitPass;
 pub use blit_texture_pass::BlitTexturePass;
+pub use bloom::{BloomBindGroups, BloomPass};
+pub use compaction::RayCompactionPass;
+pub use cryptomatte::CryptomattePass;
 pub use denoise::*;
+pub use exposure::AutoExposurePass;
 pub use intersector::IntersectorPass;
 pub use lightmap::LightmapPass;
+pub use material_sort::MaterialSortPass;
 pub use ray::RayPass;
-pub use shading::{PrimaryRayPass, ShadingPass};
+pub use shading::{DebugView, PrimaryRayPass, ShadingPass, ShadingPermutations};
 pub use temporal_accumulation::TemporalAccumulationPass;
 
 pub(crate) const GBUFFER_READ_TY: wgpu::BindingType = wgpu::BindingType::Texture {
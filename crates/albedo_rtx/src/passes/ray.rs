@@ -17,6 +17,9 @@ pub struct RayPass {
 ///
 /// This pass fills a buffer of [`uniforms::Ray`] structures based
 /// on the camera information.
+///
+/// The projection is read from [`uniforms::Camera::projection`], and can
+/// thus be changed at runtime without re-creating the pipeline.
 impl RayPass {
     const RAY_BINDING: u32 = 0;
     const CAMERA_BINDING: u32 = 1;
@@ -7,9 +7,11 @@ use crate::DenoiseResources;
 use crate::RTGeometryBindGroupLayout;
 use crate::RTSurfaceBindGroupLayout;
 use crate::RaytraceResources;
+use crate::SpectralResources;
 use albedo_backend::data::CompileError;
 use albedo_backend::data::PreprocessError;
 use albedo_backend::data::ShaderCache;
+use albedo_backend::gpu;
 use bitflags::bitflags;
 use wgpu::naga::FastHashMap;
 use wgpu::PushConstantRange;
@@ -19,6 +21,10 @@ use super::GBUFFER_WRITE_TY;
 bitflags! {
     pub struct ShadingFlags: u32 {
         const EMIT_GBUFFER = 0b00000001;
+        const SPECTRAL = 0b00000010;
+        const AOV = 0b00000100;
+        const USER_MATERIALS = 0b00001000;
+        const PROCEDURAL_TEXTURES = 0b00010000;
     }
 }
 
@@ -33,6 +39,11 @@ impl ShadingBindGroupLayout {
     const PER_DRAW_STRUCT_BINDING: u32 = 2;
     const GBUFFER_BINDING: u32 = 3;
     const MOTION_BINDING: u32 = 4;
+    const RGB_TO_SPECTRUM_BINDING: u32 = 5;
+    const SPECTRA_BINDING: u32 = 6;
+    const AOV_BINDING: u32 = 7;
+    const MATERIAL_PARAMETERS_BINDING: u32 = 8;
+    const PROCEDURAL_NODES_BINDING: u32 = 9;
 
     pub fn new(device: &wgpu::Device, defines: &FastHashMap<String, String>) -> Self {
         let flags = {
@@ -40,6 +51,18 @@ impl ShadingBindGroupLayout {
             if defines.contains_key("EMIT_GBUFFER") {
                 f = f | ShadingFlags::EMIT_GBUFFER;
             }
+            if defines.contains_key("SPECTRAL") {
+                f = f | ShadingFlags::SPECTRAL;
+            }
+            if defines.contains_key("AOV") {
+                f = f | ShadingFlags::AOV;
+            }
+            if defines.contains_key("USER_MATERIALS") {
+                f = f | ShadingFlags::USER_MATERIALS;
+            }
+            if defines.contains_key("PROCEDURAL_TEXTURES") {
+                f = f | ShadingFlags::PROCEDURAL_TEXTURES;
+            }
             f
         };
 
@@ -98,6 +121,70 @@ impl ShadingBindGroupLayout {
             ]);
         }
 
+        if flags.contains(ShadingFlags::SPECTRAL) {
+            entries.extend_from_slice(&[
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::RGB_TO_SPECTRUM_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: Self::SPECTRA_BINDING,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ]);
+        }
+
+        if flags.contains(ShadingFlags::AOV) {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::AOV_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    format: wgpu::TextureFormat::Rgba32Float,
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                },
+                count: None,
+            });
+        }
+
+        if flags.contains(ShadingFlags::USER_MATERIALS) {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::MATERIAL_PARAMETERS_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D1,
+                    multisampled: false,
+                },
+                count: None,
+            });
+        }
+
+        if flags.contains(ShadingFlags::PROCEDURAL_TEXTURES) {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::PROCEDURAL_NODES_BINDING,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Uint,
+                    view_dimension: wgpu::TextureViewDimension::D1,
+                    multisampled: false,
+                },
+                count: None,
+            });
+        }
+
         Self {
             inner: device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Shading View Bind Group Layout"),
@@ -112,6 +199,10 @@ impl ShadingBindGroupLayout {
         device: &wgpu::Device,
         resources: &RaytraceResources,
         denoise: Option<&DenoiseResources>,
+        spectral: Option<&SpectralResources>,
+        aovs: Option<&wgpu::TextureView>,
+        material_parameters: Option<&wgpu::TextureView>,
+        procedural_nodes: Option<&wgpu::TextureView>,
     ) -> wgpu::BindGroup {
         let mut entries: Vec<wgpu::BindGroupEntry<'_>> = Vec::new();
 
@@ -144,6 +235,50 @@ impl ShadingBindGroupLayout {
             });
         }
 
+        if self.flags.contains(ShadingFlags::SPECTRAL) {
+            let Some(spectral) = spectral else {
+                panic!("Spectral shading requires the spectral resources")
+            };
+            entries.push(wgpu::BindGroupEntry {
+                binding: Self::RGB_TO_SPECTRUM_BINDING,
+                resource: wgpu::BindingResource::TextureView(spectral.rgb_to_spectrum),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: Self::SPECTRA_BINDING,
+                resource: wgpu::BindingResource::TextureView(spectral.spectra),
+            });
+        }
+
+        if self.flags.contains(ShadingFlags::AOV) {
+            let Some(aovs) = aovs else {
+                panic!("AOV shading requires a texture created with `Aovs::create_texture`")
+            };
+            entries.push(wgpu::BindGroupEntry {
+                binding: Self::AOV_BINDING,
+                resource: wgpu::BindingResource::TextureView(aovs),
+            });
+        }
+
+        if self.flags.contains(ShadingFlags::USER_MATERIALS) {
+            let Some(parameters) = material_parameters else {
+                panic!("User materials require a texture created with `UserMaterials::create_parameters_texture`")
+            };
+            entries.push(wgpu::BindGroupEntry {
+                binding: Self::MATERIAL_PARAMETERS_BINDING,
+                resource: wgpu::BindingResource::TextureView(parameters),
+            });
+        }
+
+        if self.flags.contains(ShadingFlags::PROCEDURAL_TEXTURES) {
+            let Some(nodes) = procedural_nodes else {
+                panic!("Procedural textures require a texture created with `ProceduralTextures::create_texture`")
+            };
+            entries.push(wgpu::BindGroupEntry {
+                binding: Self::PROCEDURAL_NODES_BINDING,
+                resource: wgpu::BindingResource::TextureView(nodes),
+            });
+        }
+
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Radiance Estimator Frame Bind Group"),
             layout: &self.inner,
@@ -227,6 +362,30 @@ impl ShadingPass {
         pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
     }
 
+    /// Dispatch one invocation per active ray, using the header written by
+    /// [`super::RayCompactionPass`].
+    ///
+    /// The pass must be created with the `RAY_COMPACTION` define, and the
+    /// intersections written by [`super::IntersectorPass::new_compacted`].
+    pub fn dispatch_indirect(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        geometry_bindgroup: &wgpu::BindGroup,
+        surface_bindgroup: &wgpu::BindGroup,
+        frame_bind_groups: &wgpu::BindGroup,
+        active_rays: &gpu::Buffer<u32>,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Shading Indirect Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, geometry_bindgroup, &[]);
+        pass.set_bind_group(1, surface_bindgroup, &[]);
+        pass.set_bind_group(2, frame_bind_groups, &[]);
+        pass.dispatch_workgroups_indirect(active_rays.inner(), 0);
+    }
+
     pub fn new_raw(
         device: &wgpu::Device,
         geometry_layout: &RTGeometryBindGroupLayout,
@@ -272,6 +431,102 @@ impl ShadingPass {
     }
 }
 
+/// Debug visualization of the [`ShadingPass`].
+///
+/// Each view is a shader permutation, see [`ShadingPermutations`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    /// World space shading normal, after normal mapping.
+    ShadingNormal,
+    /// World space geometric normal, following the triangle winding.
+    GeometricNormal,
+    /// First texture coordinates.
+    Uv,
+    /// Barycentric coordinates of the hit.
+    Barycentric,
+    /// Material index, as a distinct color per index.
+    MaterialIndex,
+    /// Instance index, as a distinct color per index.
+    InstanceId,
+    /// Number of BVH traversal steps of the camera rays, as a heatmap.
+    BvhTraversal,
+    /// Shaded result, with paths producing a NaN or an infinity in magenta.
+    NanInf,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 8] = [
+        DebugView::ShadingNormal,
+        DebugView::GeometricNormal,
+        DebugView::Uv,
+        DebugView::Barycentric,
+        DebugView::MaterialIndex,
+        DebugView::InstanceId,
+        DebugView::BvhTraversal,
+        DebugView::NanInf,
+    ];
+
+    /// Shader define enabling the view.
+    pub fn define(&self) -> &'static str {
+        match self {
+            DebugView::ShadingNormal => "DEBUG_SHADING_NORMAL",
+            DebugView::GeometricNormal => "DEBUG_GEOMETRIC_NORMAL",
+            DebugView::Uv => "DEBUG_UV",
+            DebugView::Barycentric => "DEBUG_BARYCENTRIC",
+            DebugView::MaterialIndex => "DEBUG_MATERIAL_INDEX",
+            DebugView::InstanceId => "DEBUG_INSTANCE_ID",
+            DebugView::BvhTraversal => "DEBUG_CWBVH_TRAVERSAL",
+            DebugView::NanInf => "DEBUG_NAN_INF",
+        }
+    }
+}
+
+/// Shading passes sharing the same defines, with one permutation per
+/// [`DebugView`].
+///
+/// Permutations are compiled on first use, and cached. Debug views don't
+/// change the bindings, the same bind groups can thus be used with any
+/// permutation, and the view can be changed every frame.
+pub struct ShadingPermutations {
+    defines: FastHashMap<String, String>,
+    passes: FastHashMap<Option<DebugView>, ShadingPass>,
+}
+
+impl ShadingPermutations {
+    pub fn new(defines: FastHashMap<String, String>) -> Self {
+        Self {
+            defines,
+            passes: FastHashMap::default(),
+        }
+    }
+
+    /// Get the pass of a view, `None` for the regular shading.
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        processor: &ShaderCache,
+        view: Option<DebugView>,
+        geometry_layout: &RTGeometryBindGroupLayout,
+        surface_layout: &RTSurfaceBindGroupLayout,
+    ) -> Result<&ShadingPass, CompileError> {
+        if !self.passes.contains_key(&view) {
+            let mut defines = self.defines.clone();
+            if let Some(view) = view {
+                defines.insert(view.define().into(), "".into());
+            }
+            let pass =
+                ShadingPass::new(device, processor, &defines, geometry_layout, surface_layout)?;
+            self.passes.insert(view, pass);
+        }
+        Ok(&self.passes[&view])
+    }
+
+    /// Drop the compiled permutations, e.g., after a shader reload.
+    pub fn clear(&mut self) {
+        self.passes.clear();
+    }
+}
+
 pub struct PrimaryRayPass(ShadingPass);
 
 impl PrimaryRayPass {
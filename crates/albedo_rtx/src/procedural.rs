@@ -0,0 +1,301 @@
+//! Procedural textures.
+//!
+//! Procedural textures are small graphs of nodes, interpreted by the
+//! [`crate::passes::ShadingPass`] in place of atlas textures. They are
+//! evaluated at the unwrapped, i.e., not repeated, texture coordinates.
+//!
+//! Node outputs are `vec4`, encoded like atlas texels, e.g., in sRGB
+//! for albedo textures.
+
+use glam::{Vec2, Vec4};
+use wgpu::naga::FastHashMap;
+use wgpu::util::DeviceExt;
+
+/// Output of a node, used as input of the following ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(u32);
+
+/// Procedural texture node.
+///
+/// Patterns blend their inputs `a` and `b` with a factor in `[0; 1]`.
+/// Unconnected inputs default to black for `a`, and white for `b`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProceduralNode {
+    Constant(Vec4),
+    /// Alternates `a` and `b` every `1 / scale` in UV space.
+    Checker {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+        scale: f32,
+    },
+    /// Linear gradient, with factor `dot(uv, direction) + offset`.
+    Gradient {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+        direction: Vec2,
+        offset: f32,
+    },
+    /// Perlin noise, summed over `octaves` octaves.
+    Noise {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+        scale: f32,
+        octaves: u32,
+    },
+    /// Distance to the closest cell point, jittered by `randomness` in `[0; 1]`.
+    Voronoi {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+        scale: f32,
+        randomness: f32,
+    },
+    /// Blends by the red channel of `factor`, or by `amount` if unconnected.
+    Mix {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+        factor: Option<NodeId>,
+        amount: f32,
+    },
+    /// Product of `a` and `b`, unconnected inputs are white.
+    Multiply {
+        a: Option<NodeId>,
+        b: Option<NodeId>,
+    },
+}
+
+impl ProceduralNode {
+    /// Node type, matching `imports/procedural.glsl`.
+    fn kind(&self) -> u32 {
+        match self {
+            ProceduralNode::Constant(_) => 0,
+            ProceduralNode::Checker { .. } => 1,
+            ProceduralNode::Gradient { .. } => 2,
+            ProceduralNode::Noise { .. } => 3,
+            ProceduralNode::Voronoi { .. } => 4,
+            ProceduralNode::Mix { .. } => 5,
+            ProceduralNode::Multiply { .. } => 6,
+        }
+    }
+
+    fn inputs(&self) -> [Option<NodeId>; 3] {
+        match *self {
+            ProceduralNode::Constant(_) => [None, None, None],
+            ProceduralNode::Checker { a, b, .. }
+            | ProceduralNode::Gradient { a, b, .. }
+            | ProceduralNode::Noise { a, b, .. }
+            | ProceduralNode::Voronoi { a, b, .. }
+            | ProceduralNode::Multiply { a, b } => [a, b, None],
+            ProceduralNode::Mix { a, b, factor, .. } => [a, b, factor],
+        }
+    }
+
+    fn parameters(&self) -> Vec4 {
+        match *self {
+            ProceduralNode::Constant(color) => color,
+            ProceduralNode::Checker { scale, .. } => Vec4::new(scale, 0.0, 0.0, 0.0),
+            ProceduralNode::Gradient {
+                direction, offset, ..
+            } => Vec4::new(direction.x, direction.y, offset, 0.0),
+            ProceduralNode::Noise { scale, octaves, .. } => {
+                Vec4::new(scale, octaves as f32, 0.0, 0.0)
+            }
+            ProceduralNode::Voronoi {
+                scale, randomness, ..
+            } => Vec4::new(scale, randomness, 0.0, 0.0),
+            ProceduralNode::Mix { amount, .. } => Vec4::new(amount, 0.0, 0.0, 0.0),
+            ProceduralNode::Multiply { .. } => Vec4::ZERO,
+        }
+    }
+}
+
+/// Node graph of a procedural texture.
+///
+/// The last added node is the output of the texture.
+#[derive(Clone, Debug, Default)]
+pub struct ProceduralTexture {
+    nodes: Vec<ProceduralNode>,
+}
+
+impl ProceduralTexture {
+    /// Maximum number of nodes per texture.
+    pub const MAX_NODES: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node, and return its output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture is full, or if an input isn't a node of
+    /// this texture.
+    pub fn add(&mut self, node: ProceduralNode) -> NodeId {
+        assert!(
+            self.nodes.len() < Self::MAX_NODES,
+            "Procedural textures are limited to {} nodes",
+            Self::MAX_NODES
+        );
+        for input in node.inputs().iter().flatten() {
+            assert!(
+                (input.0 as usize) < self.nodes.len(),
+                "Input {:?} isn't a node of the texture",
+                input
+            );
+        }
+        self.nodes.push(node);
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Error returned when adding a procedural texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProceduralTextureError {
+    /// The node texture can't store more than `max_nodes` nodes.
+    TooManyNodes { max_nodes: u32 },
+}
+
+impl std::fmt::Display for ProceduralTextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyNodes { max_nodes } => {
+                write!(
+                    f,
+                    "Procedural textures are limited to {} nodes in total",
+                    max_nodes
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProceduralTextureError {}
+
+/// Procedural textures of a scene, uploaded in a single node texture.
+#[derive(Clone, Debug)]
+pub struct ProceduralTextures {
+    texels: Vec<[u32; 4]>,
+    max_nodes: u32,
+}
+
+impl Default for ProceduralTextures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProceduralTextures {
+    /// Flag set on the texture indices of procedural textures.
+    pub const TEXTURE_BIT: u32 = 0x80000000;
+
+    /// Largest node count addressable by the texture indices.
+    pub const MAX_ENCODED_NODES: u32 = 1 << 24;
+
+    /// Create textures limited by the default [`wgpu::Limits`].
+    pub fn new() -> Self {
+        Self::with_max_texture_dimension(wgpu::Limits::default().max_texture_dimension_1d)
+    }
+
+    /// Create textures limited by the limits of `device`.
+    pub fn from_limits(device: &wgpu::Device) -> Self {
+        Self::with_max_texture_dimension(device.limits().max_texture_dimension_1d)
+    }
+
+    /// Create textures stored in a node texture of at most `width` texels.
+    pub fn with_max_texture_dimension(width: u32) -> Self {
+        Self {
+            texels: Vec::new(),
+            max_nodes: (width / 2).min(Self::MAX_ENCODED_NODES),
+        }
+    }
+
+    /// Number of nodes that fit in the node texture, for all textures.
+    pub fn max_nodes(&self) -> u32 {
+        self.max_nodes
+    }
+
+    /// Add a texture, and return the index to store in the material,
+    /// e.g., in [`crate::Material::albedo_texture`].
+    ///
+    /// Fails if the nodes of all textures don't fit in the node texture.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture is empty.
+    pub fn add(&mut self, texture: &ProceduralTexture) -> Result<u32, ProceduralTextureError> {
+        assert!(!texture.is_empty(), "Procedural texture without nodes");
+        let first = (self.texels.len() / 2) as u32;
+        if first as usize + texture.len() > self.max_nodes as usize {
+            return Err(ProceduralTextureError::TooManyNodes {
+                max_nodes: self.max_nodes,
+            });
+        }
+
+        for node in &texture.nodes {
+            let [a, b, c] = node.inputs().map(|input| match input {
+                Some(id) => id.0,
+                None => crate::INVALID_INDEX,
+            });
+            self.texels.push([node.kind(), a, b, c]);
+            self.texels
+                .push(node.parameters().to_array().map(f32::to_bits));
+        }
+        Ok(Self::TEXTURE_BIT | ((texture.len() as u32) << 24) | first)
+    }
+
+    /// Texels of the node texture, two per node.
+    pub fn texels(&self) -> &[[u32; 4]] {
+        &self.texels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texels.is_empty()
+    }
+
+    pub fn insert_defines(&self, defines: &mut FastHashMap<String, String>) {
+        if !self.is_empty() {
+            defines.insert("PROCEDURAL_TEXTURES".into(), "".into());
+        }
+    }
+
+    /// Create the node texture, bound to the [`crate::passes::ShadingPass`].
+    ///
+    /// Each node takes two texels, the node count is thus limited by
+    /// [`wgpu::Limits::max_texture_dimension_1d`], see [`Self::from_limits`].
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        // Textures can't be empty.
+        let fallback = [[0; 4]; 2];
+        let texels = if self.texels.is_empty() {
+            &fallback[..]
+        } else {
+            &self.texels[..]
+        };
+        device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Procedural Texture Nodes"),
+                size: wgpu::Extent3d {
+                    width: texels.len() as u32,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D1,
+                format: wgpu::TextureFormat::Rgba32Uint,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(texels),
+        )
+    }
+}
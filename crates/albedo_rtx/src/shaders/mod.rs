@@ -1,5 +1,9 @@
 use rust_embed::RustEmbed;
 
+mod user_materials;
+
+pub use user_materials::UserMaterials;
+
 #[derive(RustEmbed)]
 #[folder = "shaders/imports"]
 #[prefix = "imports/"]
@@ -0,0 +1,171 @@
+use albedo_backend::data::ShaderCache;
+use wgpu::naga::FastHashMap;
+
+/// Materials implemented by applications, in GLSL.
+///
+/// Each material is a snippet defining two functions, prefixed by the
+/// material name:
+///
+/// ```glsl
+/// // BSDF times the cosine, `wo` toward the eye and `wi` toward the light.
+/// vec3 toon_evaluate(const UserSurface surface, const vec3 wo, const vec3 wi);
+/// // Sample `wi`, with its solid angle `pdf`, and the sampled lobe,
+/// // e.g., `LOBE_DIFFUSE`.
+/// vec3 toon_sample(const UserSurface surface, const vec3 wo, out float pdf, out uint lobe, inout uint seed);
+/// ```
+///
+/// The path is weighted by `toon_evaluate(surface, wo, wi) / pdf`, Dirac
+/// lobes can thus not be expressed.
+///
+/// Snippets are added to the [`ShaderCache`], and a dispatch on the material
+/// type is generated in [`UserMaterials::IMPORT`]. Extra parameters are read
+/// with `userMaterialParameter(surface, i)`, see `shading.comp`.
+///
+/// Registering must happen after the embedded imports are added to the cache,
+/// since they contain a default version of the dispatch. The shading pass
+/// must then be created with [`UserMaterials::insert_defines`].
+///
+/// In spectral mode, the evaluated BSDF is upsampled like an emission color.
+#[derive(Clone, Debug, Default)]
+pub struct UserMaterials {
+    names: Vec<String>,
+}
+
+impl UserMaterials {
+    /// Import containing the generated dispatch.
+    pub const IMPORT: &'static str = "imports/user_materials.glsl";
+
+    /// Material type of the built-in principled BSDF.
+    pub const PRINCIPLED: u32 = 0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the snippet `source` of the material `name`, and return its
+    /// type, to store in [`crate::Material::material_type`].
+    ///
+    /// Registering an existing name replaces its snippet, and keeps its type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a valid GLSL identifier.
+    pub fn register(&mut self, processor: &mut ShaderCache, name: &str, source: &str) -> u32 {
+        let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        assert!(valid, "Material name '{}' isn't a GLSL identifier", name);
+
+        processor.add_raw(&Self::snippet_path(name), source);
+        let index = match self.names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        };
+        processor.add_raw(Self::IMPORT, &self.generate(processor));
+        index as u32 + 1
+    }
+
+    /// Type of a registered material.
+    pub fn material_type(&self, name: &str) -> Option<u32> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|index| index as u32 + 1)
+    }
+
+    pub fn insert_defines(&self, defines: &mut FastHashMap<String, String>) {
+        if !self.names.is_empty() {
+            defines.insert("USER_MATERIALS".into(), "".into());
+        }
+    }
+
+    /// Create the side texture storing the parameters of all user materials.
+    ///
+    /// Each parameter is a `vec4`, materials point to their first one with
+    /// [`crate::Material::parameters`]. The count is limited by
+    /// [`wgpu::Limits::max_texture_dimension_1d`].
+    pub fn create_parameters_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        parameters: &[[f32; 4]],
+    ) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: parameters.len().max(1) as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("User Material Parameters"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D1,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        if !parameters.is_empty() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(parameters),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(parameters.len() as u32 * 16),
+                    rows_per_image: None,
+                },
+                size,
+            );
+        }
+        texture
+    }
+
+    fn snippet_path(name: &str) -> String {
+        format!("user_materials/{}.glsl", name)
+    }
+
+    /// Inline the snippets, since imports can't include other imports,
+    /// followed by the dispatch.
+    fn generate(&self, processor: &ShaderCache) -> String {
+        let mut code = String::from(
+            "#ifndef USER_MATERIALS_H\n#define USER_MATERIALS_H\n\n// Generated by `UserMaterials`.\n\n",
+        );
+        for name in &self.names {
+            code.push_str(processor.get(&Self::snippet_path(name)).unwrap_or_default());
+            code.push('\n');
+        }
+
+        code.push_str(
+            "vec3\nevaluateUserMaterial(uint type, const UserSurface surface, const vec3 wo, const vec3 wi)\n{\n  switch (type)\n  {\n",
+        );
+        for (index, name) in self.names.iter().enumerate() {
+            code.push_str(&format!(
+                "    case {}u: return {}_evaluate(surface, wo, wi);\n",
+                index + 1,
+                name
+            ));
+        }
+        code.push_str("    default: return vec3(0.0);\n  }\n}\n\n");
+
+        code.push_str(
+            "vec3\nsampleUserMaterial(uint type, const UserSurface surface, const vec3 wo, out float pdf, out uint lobe, inout uint seed)\n{\n  switch (type)\n  {\n",
+        );
+        for (index, name) in self.names.iter().enumerate() {
+            code.push_str(&format!(
+                "    case {}u: return {}_sample(surface, wo, pdf, lobe, seed);\n",
+                index + 1,
+                name
+            ));
+        }
+        code.push_str(
+            "    default:\n      pdf = 0.0;\n      lobe = LOBE_DIFFUSE;\n      return surface.normal;\n  }\n}\n\n#endif // USER_MATERIALS_H\n",
+        );
+        code
+    }
+}
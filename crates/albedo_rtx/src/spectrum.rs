@@ -0,0 +1,482 @@
+//! Spectral rendering utilities.
+//!
+//! Spectra are defined over [[`LAMBDA_MIN`]; [`LAMBDA_MAX`]], in nanometers.
+//!
+//! RGB colors use the linear sRGB primaries with an equal-energy white point,
+//! i.e., a constant spectrum of `1` maps to an RGB white of `1`. This keeps
+//! white materials and lights white when switching to spectral rendering.
+
+use glam::{DMat3, DVec3, Vec3};
+use wgpu::util::DeviceExt;
+
+/// Lower bound of the visible wavelengths, in nanometers.
+pub const LAMBDA_MIN: f32 = 380.0;
+/// Upper bound of the visible wavelengths, in nanometers.
+pub const LAMBDA_MAX: f32 = 780.0;
+
+/// Number of samples of a [`Spectrum`], spaced every 5nm.
+pub const SPECTRUM_SAMPLES: usize = 81;
+
+/// Integral of the fit of the CIE color matching functions over the
+/// visible wavelengths.
+const CIE_INTEGRAL: [f64; 3] = [106.765_046, 106.919_735, 106.825_325];
+
+/// Number of wavelengths used to integrate spectra when fitting the table.
+const FIT_SAMPLES: usize = 100;
+
+fn gaussian_lobe(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if x < mu { sigma_low } else { sigma_high };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+fn cie_xyz_f64(lambda: f64) -> DVec3 {
+    let x = 1.056 * gaussian_lobe(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * gaussian_lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * gaussian_lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * gaussian_lobe(lambda, 568.8, 46.9, 40.5)
+        + 0.286 * gaussian_lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * gaussian_lobe(lambda, 437.0, 11.8, 36.0)
+        + 0.681 * gaussian_lobe(lambda, 459.0, 26.0, 13.8);
+    DVec3::new(
+        x / CIE_INTEGRAL[0],
+        y / CIE_INTEGRAL[1],
+        z / CIE_INTEGRAL[2],
+    )
+}
+
+/// CIE 1931 color matching functions, normalized to integrate to `1`.
+///
+/// This method is based on:
+///  - Simple Analytic Approximations to the CIE XYZ Color Matching Functions, Wyman et al. 2013
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    cie_xyz_f64(lambda as f64).as_vec3()
+}
+
+fn xyz_to_rgb_matrix() -> DMat3 {
+    // sRGB primaries, with a Bradford adaptation from the equal-energy
+    // white point to D65.
+    DMat3::from_cols_array(&[
+        3.146_251_0,
+        -0.995_535_0,
+        0.063_597_8,
+        -1.666_123_9,
+        1.955_763_4,
+        -0.214_596_5,
+        -0.480_127_1,
+        0.039_771_5,
+        1.150_998_7,
+    ])
+}
+
+/// Convert a CIE XYZ color to linear RGB.
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    (xyz_to_rgb_matrix() * xyz.as_dvec3()).as_vec3()
+}
+
+/// Convert a linear RGB color to CIE XYZ.
+pub fn rgb_to_xyz(rgb: Vec3) -> Vec3 {
+    (xyz_to_rgb_matrix().inverse() * rgb.as_dvec3()).as_vec3()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+/// Evaluate a spectrum stored as the coefficients of a sigmoid polynomial.
+///
+/// The polynomial is evaluated with the wavelength normalized in `[0; 1]`.
+pub fn eval_sigmoid_polynomial(coefficients: Vec3, lambda: f32) -> f32 {
+    let t = ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN)) as f64;
+    let c = coefficients.as_dvec3();
+    sigmoid((c.x * t + c.y) * t + c.z) as f32
+}
+
+fn smoothstep(x: f64) -> f64 {
+    x * x * (3.0 - 2.0 * x)
+}
+
+fn inverse_smoothstep(y: f64) -> f64 {
+    0.5 - ((1.0 - 2.0 * y).asin() / 3.0).sin()
+}
+
+/// Integrates sigmoid polynomials against the color matching functions.
+struct Fit {
+    wavelengths: Vec<f64>,
+    weights: Vec<DVec3>,
+    rgb_to_xyz: DMat3,
+}
+
+impl Fit {
+    fn new() -> Self {
+        let range = (LAMBDA_MAX - LAMBDA_MIN) as f64;
+        let step = range / FIT_SAMPLES as f64;
+        let wavelengths: Vec<f64> = (0..FIT_SAMPLES)
+            .map(|i| (i as f64 + 0.5) / FIT_SAMPLES as f64)
+            .collect();
+        let weights = wavelengths
+            .iter()
+            .map(|t| cie_xyz_f64(LAMBDA_MIN as f64 + t * range) * step)
+            .collect();
+        Self {
+            wavelengths,
+            weights,
+            rgb_to_xyz: xyz_to_rgb_matrix().inverse(),
+        }
+    }
+
+    fn xyz(&self, c: DVec3) -> DVec3 {
+        self.wavelengths
+            .iter()
+            .zip(self.weights.iter())
+            .fold(DVec3::ZERO, |acc, (t, w)| {
+                acc + *w * sigmoid((c.x * t + c.y) * t + c.z)
+            })
+    }
+
+    fn lab(xyz: DVec3) -> DVec3 {
+        // The white point is the equal-energy illuminant, i.e., `(1, 1, 1)`.
+        const DELTA: f64 = 6.0 / 29.0;
+        let f = |t: f64| {
+            if t > DELTA * DELTA * DELTA {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        };
+        let (x, y, z) = (f(xyz.x), f(xyz.y), f(xyz.z));
+        DVec3::new(116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z))
+    }
+
+    fn residual(&self, c: DVec3, target: DVec3) -> DVec3 {
+        target - Self::lab(self.xyz(c))
+    }
+
+    /// Gauss-Newton minimization of the CIELAB distance to `rgb`.
+    fn solve(&self, rgb: DVec3, c: &mut DVec3) {
+        const EPSILON: f64 = 1e-5;
+        let target = Self::lab(self.rgb_to_xyz * rgb);
+        for _ in 0..15 {
+            let residual = self.residual(*c, target);
+            if residual.length_squared() < 1e-6 {
+                break;
+            }
+            let mut jacobian = DMat3::ZERO;
+            for i in 0..3 {
+                let mut offset = DVec3::ZERO;
+                offset[i] = EPSILON;
+                let r0 = self.residual(*c - offset, target);
+                let r1 = self.residual(*c + offset, target);
+                *jacobian.col_mut(i) = (r1 - r0) / (2.0 * EPSILON);
+            }
+            if jacobian.determinant().abs() < 1e-15 {
+                break;
+            }
+            *c -= jacobian.inverse() * residual;
+
+            // Keep coefficients in a reasonable range.
+            let max = c.abs().max_element();
+            if max > 200.0 {
+                *c *= 200.0 / max;
+            }
+        }
+    }
+}
+
+/// Table mapping RGB reflectances to spectra.
+///
+/// Each entry stores the 3 coefficients of a sigmoid polynomial, see
+/// [`eval_sigmoid_polynomial`]. The table is indexed by the largest RGB
+/// component, its value, and the two other components divided by it.
+/// Values are remapped to concentrate entries near black and white.
+///
+/// This method is based on:
+///  - A Low-Dimensional Function Space for Efficient Spectral Upsampling, Jakob and Hanika 2019
+pub struct RgbToSpectrumTable {
+    resolution: u32,
+    coefficients: Vec<[f32; 4]>,
+}
+
+impl RgbToSpectrumTable {
+    /// Fit the table, with `resolution` entries along each dimension.
+    ///
+    /// **Note**: This runs an optimization per entry. A resolution of `32`
+    /// is a good compromise between accuracy and build time.
+    pub fn new(resolution: u32) -> Self {
+        assert!(resolution >= 2, "resolution must be at least 2");
+        let res = resolution as usize;
+        let fit = Fit::new();
+        let scale: Vec<f64> = (0..res)
+            .map(|k| smoothstep(smoothstep(k as f64 / (res - 1) as f64)))
+            .collect();
+
+        let mut coefficients = vec![[0.0; 4]; 3 * res * res * res];
+        let index = |l: usize, k: usize, j: usize, i: usize| ((l * res + k) * res + j) * res + i;
+
+        // Solutions of neighbouring entries are used as starting points,
+        // starting from a mid-range brightness.
+        let start = res / 5;
+        for l in 0..3 {
+            for j in 0..res {
+                let y = j as f64 / (res - 1) as f64;
+                for i in 0..res {
+                    let x = i as f64 / (res - 1) as f64;
+                    let mut start_solution = DVec3::ZERO;
+                    let mut solve = |k: usize, c: &mut DVec3| {
+                        let z = scale[k];
+                        let mut rgb = DVec3::ZERO;
+                        rgb[l] = z;
+                        rgb[(l + 1) % 3] = x * z;
+                        rgb[(l + 2) % 3] = y * z;
+                        fit.solve(rgb, c);
+                        coefficients[index(l, k, j, i)] = [c.x as f32, c.y as f32, c.z as f32, 0.0];
+                    };
+
+                    let mut c = DVec3::ZERO;
+                    for k in start..res {
+                        solve(k, &mut c);
+                        if k == start {
+                            start_solution = c;
+                        }
+                    }
+                    let mut c = start_solution;
+                    for k in (0..start).rev() {
+                        solve(k, &mut c);
+                    }
+                }
+            }
+        }
+
+        Self {
+            resolution,
+            coefficients,
+        }
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Coefficients of the sigmoid polynomial of an RGB reflectance.
+    ///
+    /// Entries are interpolated trilinearly, as done in `shading.comp`.
+    pub fn coefficients(&self, rgb: Vec3) -> Vec3 {
+        let rgb = rgb.clamp(Vec3::ZERO, Vec3::ONE);
+        if rgb.x == rgb.y && rgb.y == rgb.z {
+            let v = rgb.x as f64;
+            let c = (v - 0.5) / (v * (1.0 - v)).sqrt();
+            return Vec3::new(0.0, 0.0, c as f32);
+        }
+
+        let l = if rgb.x >= rgb.y && rgb.x >= rgb.z {
+            0
+        } else if rgb.y >= rgb.z {
+            1
+        } else {
+            2
+        };
+        let z = rgb[l];
+        let res = self.resolution as usize;
+        let max = (res - 1) as f32;
+        let coords = Vec3::new(
+            rgb[(l + 1) % 3] / z * max,
+            rgb[(l + 2) % 3] / z * max,
+            inverse_smoothstep(inverse_smoothstep(z as f64)) as f32 * max,
+        );
+        let base = coords.floor().min(Vec3::splat(max - 1.0));
+        let t = coords - base;
+
+        let fetch = |i: usize, j: usize, k: usize| {
+            let [c0, c1, c2, _] = self.coefficients[((l * res + k) * res + j) * res + i];
+            Vec3::new(c0, c1, c2)
+        };
+        let (i, j, k) = (base.x as usize, base.y as usize, base.z as usize);
+        let lerp = |a: Vec3, b: Vec3, t: f32| a + (b - a) * t;
+        let c00 = lerp(fetch(i, j, k), fetch(i + 1, j, k), t.x);
+        let c10 = lerp(fetch(i, j + 1, k), fetch(i + 1, j + 1, k), t.x);
+        let c01 = lerp(fetch(i, j, k + 1), fetch(i + 1, j, k + 1), t.x);
+        let c11 = lerp(fetch(i, j + 1, k + 1), fetch(i + 1, j + 1, k + 1), t.x);
+        lerp(lerp(c00, c10, t.y), lerp(c01, c11, t.y), t.z)
+    }
+
+    /// Evaluate the spectrum of an RGB reflectance.
+    pub fn eval(&self, rgb: Vec3, lambda: f32) -> f32 {
+        eval_sigmoid_polynomial(self.coefficients(rgb), lambda)
+    }
+
+    /// Upload the table into a 3D texture, with the slabs of each largest
+    /// component stacked along the depth.
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("RGB to Spectrum Table"),
+                size: wgpu::Extent3d {
+                    width: self.resolution,
+                    height: self.resolution,
+                    depth_or_array_layers: 3 * self.resolution,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&self.coefficients),
+        )
+    }
+}
+
+/// Spectral power distribution, sampled every 5nm over the visible range.
+///
+/// Used for emitters with measured spectra, see [`crate::uniforms::Light::spectrum`]
+/// and [`crate::uniforms::RadianceParameters::environment_spectrum`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spectrum {
+    pub samples: [f32; SPECTRUM_SAMPLES],
+}
+
+impl Spectrum {
+    /// Distance between two samples, in nanometers.
+    pub const STEP: f32 = (LAMBDA_MAX - LAMBDA_MIN) / (SPECTRUM_SAMPLES - 1) as f32;
+
+    pub fn constant(value: f32) -> Self {
+        Self {
+            samples: [value; SPECTRUM_SAMPLES],
+        }
+    }
+
+    /// Resample measured `(wavelength, value)` pairs, sorted by wavelength.
+    ///
+    /// Wavelengths outside of the measurements use the closest measured value.
+    pub fn from_measured(measurements: &[(f32, f32)]) -> Self {
+        let mut spectrum = Self::constant(0.0);
+        if measurements.is_empty() {
+            return spectrum;
+        }
+        for (i, sample) in spectrum.samples.iter_mut().enumerate() {
+            let lambda = Self::wavelength(i);
+            let next = measurements.partition_point(|(l, _)| *l < lambda);
+            *sample = if next == 0 {
+                measurements[0].1
+            } else if next == measurements.len() {
+                measurements[next - 1].1
+            } else {
+                let (l0, v0) = measurements[next - 1];
+                let (l1, v1) = measurements[next];
+                let t = if l1 > l0 {
+                    (lambda - l0) / (l1 - l0)
+                } else {
+                    0.0
+                };
+                v0 + (v1 - v0) * t
+            };
+        }
+        spectrum
+    }
+
+    /// Planck's law, normalized to a peak of `1`.
+    ///
+    /// @param temperature Temperature, in Kelvin
+    pub fn blackbody(temperature: f32) -> Self {
+        const C: f64 = 299_792_458.0;
+        const H: f64 = 6.626_070_15e-34;
+        const KB: f64 = 1.380_649e-23;
+        let temperature = temperature as f64;
+        let planck = |lambda: f64| {
+            let l = lambda * 1e-9;
+            (2.0 * H * C * C) / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0))
+        };
+        // Wien's displacement law.
+        let peak = planck(2.897_771_955e-3 / temperature * 1e9);
+
+        let mut spectrum = Self::constant(0.0);
+        for (i, sample) in spectrum.samples.iter_mut().enumerate() {
+            *sample = (planck(Self::wavelength(i) as f64) / peak) as f32;
+        }
+        spectrum
+    }
+
+    /// Wavelength of the sample at `index`, in nanometers.
+    pub fn wavelength(index: usize) -> f32 {
+        LAMBDA_MIN + index as f32 * Self::STEP
+    }
+
+    pub fn eval(&self, lambda: f32) -> f32 {
+        let x = ((lambda - LAMBDA_MIN) / Self::STEP).clamp(0.0, (SPECTRUM_SAMPLES - 1) as f32);
+        let i = (x as usize).min(SPECTRUM_SAMPLES - 2);
+        let t = x - i as f32;
+        self.samples[i] + (self.samples[i + 1] - self.samples[i]) * t
+    }
+
+    pub fn to_xyz(&self) -> Vec3 {
+        // Trapezoidal integration.
+        let xyz = self
+            .samples
+            .iter()
+            .enumerate()
+            .fold(DVec3::ZERO, |acc, (i, value)| {
+                let weight = if i == 0 || i == SPECTRUM_SAMPLES - 1 {
+                    0.5
+                } else {
+                    1.0
+                };
+                let lambda = Self::wavelength(i) as f64;
+                acc + cie_xyz_f64(lambda) * (*value as f64 * weight)
+            });
+        (xyz * Self::STEP as f64).as_vec3()
+    }
+
+    pub fn to_rgb(&self) -> Vec3 {
+        xyz_to_rgb(self.to_xyz())
+    }
+
+    /// Scale the spectrum to a luminance, i.e., CIE `Y`, of `1`.
+    pub fn normalized(mut self) -> Self {
+        let y = self.to_xyz().y;
+        if y > 0.0 {
+            self.samples.iter_mut().for_each(|v| *v /= y);
+        }
+        self
+    }
+}
+
+/// Upload spectra into a texture, with one spectrum per row.
+pub fn create_spectra_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    spectra: &[Spectrum],
+) -> wgpu::Texture {
+    // Textures can't be empty.
+    let fallback = [Spectrum::constant(0.0)];
+    let spectra = if spectra.is_empty() {
+        &fallback
+    } else {
+        spectra
+    };
+    let data: Vec<f32> = spectra.iter().flat_map(|s| s.samples).collect();
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Spectra"),
+            size: wgpu::Extent3d {
+                width: SPECTRUM_SAMPLES as u32,
+                height: spectra.len() as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        bytemuck::cast_slice(&data),
+    )
+}
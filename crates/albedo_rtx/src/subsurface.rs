@@ -0,0 +1,131 @@
+//! CPU port of the random walk of `shaders/imports/subsurface.glsl`.
+//!
+//! The scene traversal is replaced by a closure, so that walks can be
+//! validated inside analytic shapes, e.g., with a white furnace test.
+
+use glam::Vec3;
+
+use crate::bsdf::{fresnel_dielectric, rand, reflect, refract, EPSILON, TWO_PI};
+use crate::uniforms::Medium;
+
+/// Walks longer than this are considered absorbed, see
+/// `SUBSURFACE_MAX_STEPS` in the shader.
+pub const SUBSURFACE_MAX_STEPS: u32 = 256;
+
+/// Surface reached by a walk, i.e., the distance along the walk direction and
+/// the geometric normal, facing either side.
+pub type SurfaceHit = (f32, Vec3);
+
+/// Exit point of a walk, see [`random_walk_subsurface`].
+#[derive(Clone, Copy, Debug)]
+pub struct SubsurfaceExit {
+    pub position: Vec3,
+    /// Refracted direction leaving the mesh.
+    pub dir: Vec3,
+    /// Geometric normal at the exit point, facing outside.
+    pub normal: Vec3,
+    pub throughput: Vec3,
+}
+
+/// Medium yielding the multiple scattering albedo `color`, with a mean free
+/// path of `radius`, see `subsurfaceMedium` in the shader.
+pub fn subsurface_medium(color: Vec3, radius: Vec3) -> Medium {
+    let color = color.clamp(Vec3::ZERO, Vec3::splat(0.999));
+    let s =
+        4.09712 + 4.20863 * color - (9.59217 + 41.6808 * color + 17.7126 * color * color).powf(0.5);
+    let albedo = Vec3::ONE - s * s;
+
+    let sigma_t = Vec3::ONE / radius.max(Vec3::splat(1e-6));
+    let scattering = sigma_t * albedo;
+    Medium::new(sigma_t - scattering, scattering, 0.0)
+}
+
+/// Samples a collision distance in a homogeneous medium.
+///
+/// Returns the distance, `t_max` if the surface is reached first, and the
+/// sample weight.
+pub fn sample_free_flight(medium: &Medium, t_max: f32, seed: &mut u32) -> (f32, Vec3) {
+    let sigma_t = medium.absorption + medium.scattering;
+    let majorant = sigma_t.max_element();
+    if majorant <= EPSILON {
+        return (t_max, Vec3::ONE);
+    }
+
+    let t = -(1.0 - rand(seed)).max(EPSILON).ln() / majorant;
+    if t >= t_max {
+        // Ratio tracking, closed-form for homogeneous media.
+        return (t_max, ((majorant - sigma_t) * t_max).exp());
+    }
+    let weight = ((majorant - sigma_t) * t).exp() * medium.scattering / majorant;
+    (t, weight)
+}
+
+/// Samples the Henyey-Greenstein phase function around the propagation
+/// direction `dir`. The weight is `1`.
+pub fn sample_henyey_greenstein(dir: Vec3, g: f32, seed: &mut u32) -> Vec3 {
+    let u = rand(seed);
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * u
+    } else {
+        let sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        (1.0 + g * g - sq * sq) / (2.0 * g)
+    }
+    .clamp(-1.0, 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = TWO_PI * rand(seed);
+
+    let world_up = if dir.z.abs() < 0.9999 {
+        Vec3::Z
+    } else {
+        Vec3::X
+    };
+    let tangent = world_up.cross(dir).normalize();
+    let bitangent = dir.cross(tangent);
+    (tangent * sin_theta * phi.cos() + bitangent * sin_theta * phi.sin() + dir * cos_theta)
+        .normalize()
+}
+
+/// Walks inside a closed shape, until the path refracts out of it through
+/// the smooth dielectric boundary of index `ior`.
+///
+/// `hit` returns the surface reached from a position along a direction,
+/// `None` for open shapes. Returns `None` if the walk didn't leave the shape.
+pub fn random_walk_subsurface(
+    medium: &Medium,
+    ior: f32,
+    mut position: Vec3,
+    mut dir: Vec3,
+    hit: impl Fn(Vec3, Vec3) -> Option<SurfaceHit>,
+    seed: &mut u32,
+) -> Option<SubsurfaceExit> {
+    let mut throughput = Vec3::ONE;
+    for _ in 0..SUBSURFACE_MAX_STEPS {
+        let (dist, normal) = hit(position, dir)?;
+
+        let (t, weight) = sample_free_flight(medium, dist, seed);
+        throughput *= weight;
+        position += t * dir;
+        if t < dist {
+            dir = sample_henyey_greenstein(dir, medium.anisotropy, seed);
+            continue;
+        }
+
+        let normal = if normal.dot(dir) > 0.0 {
+            normal
+        } else {
+            -normal
+        };
+        if rand(seed) < fresnel_dielectric(normal.dot(dir), ior) {
+            dir = reflect(dir, normal);
+            position -= normal * 1e-4;
+            continue;
+        }
+        return Some(SubsurfaceExit {
+            position,
+            dir: refract(dir, -normal, ior),
+            normal,
+            throughput,
+        });
+    }
+    None
+}
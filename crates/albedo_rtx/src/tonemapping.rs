@@ -0,0 +1,234 @@
+//! Tonemapping and display transforms.
+//!
+//! The operators match `imports/tonemapping.glsl`, used by the
+//! [`crate::passes::BlitPass`] and [`crate::passes::BlitTexturePass`].
+//! Images saved from the CPU thus match the ones displayed on screen.
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat3, Vec3, Vec4};
+
+use crate::Uniform;
+
+const MIDDLE_GREY: f32 = 0.18;
+
+/// Tonemapping operator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Clamps the radiance, scaled by the white point.
+    None = 0,
+    /// Extended Reinhard.
+    Reinhard = 1,
+    /// ACES fitted by Stephen Hill.
+    AcesFitted = 2,
+    /// AgX by Troy Sobotka.
+    AgX = 3,
+    /// Khronos PBR Neutral.
+    PbrNeutral = 4,
+    /// ACES filmic curve fitted by Krzysztof Narkowicz, without the
+    /// input and output transforms.
+    #[default]
+    AcesFilm = 5,
+}
+
+/// Tonemapping parameters of the blit passes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct TonemappingParameters {
+    pub tonemapper: u32,
+    /// Radiance mapped to white by [`Tonemapper::None`] and
+    /// [`Tonemapper::Reinhard`]. Other operators scale their input by
+    /// its inverse, `1` leaving them unchanged.
+    pub white_point: f32,
+    /// Power applied to the radiance around middle grey, `1` leaving
+    /// it unchanged.
+    pub contrast: f32,
+    pub padding: u32,
+}
+impl Uniform for TonemappingParameters {}
+
+impl TonemappingParameters {
+    pub fn new(tonemapper: Tonemapper) -> Self {
+        Self {
+            tonemapper: tonemapper as u32,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_tonemapper(&mut self, tonemapper: Tonemapper) {
+        self.tonemapper = tonemapper as u32;
+    }
+
+    pub fn tonemapper(&self) -> Tonemapper {
+        match self.tonemapper {
+            0 => Tonemapper::None,
+            1 => Tonemapper::Reinhard,
+            3 => Tonemapper::AgX,
+            2 => Tonemapper::AcesFitted,
+            4 => Tonemapper::PbrNeutral,
+            _ => Tonemapper::AcesFilm,
+        }
+    }
+}
+
+impl Default for TonemappingParameters {
+    fn default() -> Self {
+        Self {
+            tonemapper: Tonemapper::default() as u32,
+            white_point: 1.0,
+            contrast: 1.0,
+            padding: 0,
+        }
+    }
+}
+
+fn reinhard(x: Vec3, white: f32) -> Vec3 {
+    x * (Vec3::ONE + x / (white * white)) / (Vec3::ONE + x)
+}
+
+fn aces_film(x: Vec3) -> Vec3 {
+    const A: f32 = 2.51;
+    const B: f32 = 0.03;
+    const C: f32 = 2.43;
+    const D: f32 = 0.59;
+    const E: f32 = 0.14;
+    ((x * (A * x + B)) / (x * (C * x + D) + E)).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+fn aces_fitted(color: Vec3) -> Vec3 {
+    #[rustfmt::skip]
+    let input = Mat3::from_cols_array(&[
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777,
+    ]);
+    #[rustfmt::skip]
+    let output = Mat3::from_cols_array(&[
+        1.60475, -0.10208, -0.00327,
+        -0.53108, 1.10813, -0.07276,
+        -0.07367, -0.00605, 1.07602,
+    ]);
+    let color = input * color;
+    let a = color * (color + 0.0245786) - 0.000090537;
+    let b = color * (0.983729 * color + 0.432951) + 0.238081;
+    (output * (a / b)).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+// Constants are kept as published, to match the shader.
+#[allow(clippy::excessive_precision)]
+fn agx(color: Vec3) -> Vec3 {
+    #[rustfmt::skip]
+    let inset = Mat3::from_cols_array(&[
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    ]);
+    #[rustfmt::skip]
+    let outset = Mat3::from_cols_array(&[
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    ]);
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let color = (inset * color).max(Vec3::splat(1e-10));
+    let ev = Vec3::from(color.to_array().map(f32::log2));
+    let x = (ev.clamp(Vec3::splat(MIN_EV), Vec3::splat(MAX_EV)) - MIN_EV) / (MAX_EV - MIN_EV);
+
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let color =
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232;
+
+    (outset * color)
+        .max(Vec3::ZERO)
+        .powf(2.2)
+        .clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+fn pbr_neutral(color: Vec3) -> Vec3 {
+    const START_COMPRESSION: f32 = 0.8 - 0.04;
+    const DESATURATION: f32 = 0.15;
+
+    let x = color.min_element();
+    let offset = if x < 0.08 { x - 6.25 * x * x } else { 0.04 };
+    let color = color - offset;
+
+    let peak = color.max_element();
+    if peak < START_COMPRESSION {
+        return color;
+    }
+
+    let d = 1.0 - START_COMPRESSION;
+    let new_peak = 1.0 - d * d / (peak + d - START_COMPRESSION);
+    let color = color * (new_peak / peak);
+
+    let g = 1.0 - 1.0 / (DESATURATION * (peak - new_peak) + 1.0);
+    color.lerp(Vec3::splat(new_peak), g)
+}
+
+/// Map linear radiance to `[0; 1]`, before the display encoding.
+pub fn tonemap(color: Vec3, parameters: &TonemappingParameters) -> Vec3 {
+    let color = color.max(Vec3::ZERO);
+    let color = MIDDLE_GREY * (color / MIDDLE_GREY).powf(parameters.contrast);
+
+    let white = parameters.white_point.max(1e-4);
+    let tonemapper = parameters.tonemapper();
+    if tonemapper == Tonemapper::Reinhard {
+        return reinhard(color, white).clamp(Vec3::ZERO, Vec3::ONE);
+    }
+
+    let color = color / white;
+    let color = match tonemapper {
+        Tonemapper::AcesFilm => aces_film(color),
+        Tonemapper::AcesFitted => aces_fitted(color),
+        Tonemapper::AgX => agx(color),
+        Tonemapper::PbrNeutral => pbr_neutral(color),
+        Tonemapper::None | Tonemapper::Reinhard => color,
+    };
+    color.clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+/// sRGB encoding, matching `linearTosRGB` in `imports/colorspace.glsl`.
+pub fn linear_to_srgb(color: Vec3) -> Vec3 {
+    Vec3::from(color.to_array().map(|c| {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            c.powf(0.41666) * 1.055 - 0.055
+        }
+    }))
+}
+
+/// Display transform of the [`crate::passes::BlitPass`].
+///
+/// `accumulated` holds the radiance summed over samples, with the sum of
+/// the weights in alpha, and `alpha` the sum of the alpha of the samples,
+/// see [`crate::passes::AccumulationTarget`]. The radiance is scaled by
+/// `exposure`, tonemapped, and encoded to 8-bit sRGB.
+///
+/// Unlike the blit, the color isn't premultiplied, as expected by most
+/// image formats.
+pub fn display(
+    accumulated: Vec4,
+    alpha: f32,
+    exposure: f32,
+    parameters: &TonemappingParameters,
+) -> [u8; 4] {
+    let weights = accumulated.w;
+    let alpha = if weights > 0.0 {
+        (alpha / weights).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    // The radiance is premultiplied by the alpha.
+    let radiance = if weights * alpha > 0.0 {
+        accumulated.truncate() / (weights * alpha)
+    } else {
+        Vec3::ZERO
+    };
+    let color = linear_to_srgb(tonemap(radiance * exposure, parameters));
+    let [r, g, b] = color.to_array().map(|c| (c * 255.0 + 0.5) as u8);
+    [r, g, b, (alpha * 255.0 + 0.5) as u8]
+}
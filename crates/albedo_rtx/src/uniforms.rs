@@ -1,3 +1,4 @@
+use albedo_backend::data::packing::{pack_octahedral, unpack_octahedral};
 use albedo_backend::{gpu, mesh};
 use bytemuck::{Pod, Zeroable};
 
@@ -12,6 +13,43 @@ pub trait Uniform: Sized {
     }
 }
 
+/// Homogeneous participating medium.
+///
+/// Coefficients are expressed per unit of distance, in world space.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Medium {
+    /// Absorption coefficient.
+    pub absorption: glam::Vec3,
+    /// Henyey-Greenstein anisotropy, in `]-1; 1[`.
+    ///
+    /// Positive values scatter light forward.
+    pub anisotropy: f32,
+    /// Scattering coefficient.
+    pub scattering: glam::Vec3,
+    pub padding: f32,
+}
+
+impl Medium {
+    pub fn new(absorption: glam::Vec3, scattering: glam::Vec3, anisotropy: f32) -> Self {
+        Medium {
+            absorption,
+            anisotropy,
+            scattering,
+            ..Default::default()
+        }
+    }
+
+    /// Create a gray, non-absorbing, medium, e.g., fog.
+    pub fn fog(density: f32, anisotropy: f32) -> Self {
+        Medium::new(glam::Vec3::ZERO, glam::Vec3::splat(density), anisotropy)
+    }
+
+    pub fn is_vacuum(&self) -> bool {
+        (self.absorption + self.scattering).max_element() <= 0.0
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Instance {
@@ -22,6 +60,18 @@ pub struct Instance {
     pub bvh_root_index: u32,
     pub vertex_root_index: u32,
     pub bvh_primitive_index: u32,
+    /// Medium enclosed by the mesh, which must be closed.
+    ///
+    /// Rays enter the medium when refracted through the mesh. Use a fully
+    /// transmissive material with an `ior` of `1.0` for an invisible boundary.
+    pub medium: Medium,
+    /// Set to `1` to skip back faces during traversal, e.g., for
+    /// single-sided geometry.
+    ///
+    /// Rays then travel through closed meshes from inside, which must thus
+    /// not be transmissive, nor use subsurface scattering.
+    pub cull_backfaces: u32,
+    pub padding: [u32; 3],
 }
 impl Uniform for Instance {}
 
@@ -42,36 +92,218 @@ impl Instance {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy)]
 pub struct Material {
     pub color: glam::Vec4,
     pub roughness: f32,
     pub reflectivity: f32,
+    /// Atlas texture index, or procedural texture returned by
+    /// [`crate::procedural::ProceduralTextures::add`], for all textures.
     pub albedo_texture: u32,
     pub mra_texture: u32,
+    /// Amount of light transmitted through the surface, in `[0; 1]`.
+    ///
+    /// Transmission is only applied on the dielectric part of the material.
+    pub transmission: f32,
+    /// Index of refraction of the medium enclosed by the surface.
+    pub ior: f32,
+    /// Tangent-space normal map, [`INVALID_INDEX`] if none.
+    pub normal_texture: u32,
+    /// Scale applied to the X and Y components of the normal map.
+    pub normal_scale: f32,
+    /// Sheen color, in linear space. Black disables the sheen layer.
+    pub sheen_color: glam::Vec3,
+    /// Perceptual roughness of the sheen layer.
+    pub sheen_roughness: f32,
+    /// Strength of the clearcoat layer, in `[0; 1]`.
+    pub clearcoat: f32,
+    /// Perceptual roughness of the clearcoat layer.
+    pub clearcoat_roughness: f32,
+    /// Anisotropy strength, in `[0; 1]`.
+    ///
+    /// The specular roughness is stretched along the mesh tangent.
+    pub anisotropy: f32,
+    /// Rotation of the anisotropy direction around the normal, in radians.
+    pub anisotropy_rotation: f32,
+    /// Tints the dielectric specular reflection toward the base color, in `[0; 1]`.
+    pub specular_tint: f32,
+    /// Dispersion of the refracted light, `20 / V` with `V` the Abbe number,
+    /// following `KHR_materials_dispersion`.
+    ///
+    /// Only used in spectral mode, `0` disables dispersion.
+    pub dispersion: f32,
+    /// [`crate::UserMaterials::PRINCIPLED`], or the type returned by
+    /// [`crate::UserMaterials::register`].
+    pub material_type: u32,
+    /// Index of the first parameter of a user material, in the texture
+    /// created by [`crate::UserMaterials::create_parameters_texture`].
+    pub parameters: u32,
+    /// Multiple scattering albedo of the subsurface, in linear space.
+    pub subsurface_color: glam::Vec3,
+    /// Amount of the diffuse base replaced by subsurface scattering,
+    /// in `[0; 1]`. `0` disables subsurface scattering.
+    ///
+    /// Light enters the surface and randomly walks inside the mesh, which
+    /// must be closed, until it exits.
+    pub subsurface: f32,
+    /// Mean free path of each channel, in world units once multiplied
+    /// by [`Material::subsurface_scale`].
+    pub subsurface_radius: glam::Vec3,
+    pub subsurface_scale: f32,
+    /// How the surface contributes to the alpha, see [`MaterialMode`].
+    pub mode: u32,
+    /// Set to `0` for single-sided surfaces, e.g., following glTF's
+    /// `doubleSided`.
+    ///
+    /// Back faces of single-sided surfaces absorb light, and thus reveal
+    /// flipped normals. Transmissive surfaces are hit from the back by rays
+    /// leaving the volume they enclose, and are thus always double-sided.
+    /// See [`Instance::cull_backfaces`] to hide back faces instead.
+    pub double_sided: u32,
+    /// Material used for back faces, [`INVALID_INDEX`] to use this one.
+    ///
+    /// Closed meshes are hit from the back by rays travelling inside them.
+    pub back_material: u32,
+    /// Strength of the dielectric specular reflection, in `[0; 1]`, scaling
+    /// a reflectance of `0.04` at normal incidence, i.e., an IOR of `1.5`.
+    ///
+    /// [`Material::new`] leaves it to `0`, reflecting only at grazing
+    /// angles. [`Material::principled_default`] sets it to `1`, following
+    /// glTF.
+    pub specular: f32,
 }
 unsafe impl bytemuck::Pod for Material {}
 unsafe impl bytemuck::Zeroable for Material {}
 impl Uniform for Material {}
 
 impl Material {
+    /// Index of refraction used by default, matching glTF.
+    pub const DEFAULT_IOR: f32 = 1.5;
+
     pub fn new(color: glam::Vec4, roughness: f32, reflectivity: f32) -> Material {
         Material {
             color,
             roughness,
             reflectivity,
+            specular: 0.0,
+            ..Material::principled_default()
+        }
+    }
+
+    /// White, fully rough, dielectric material, without textures.
+    ///
+    /// Unlike [`Material::default`], which zeroes the color, roughness,
+    /// reflectivity, specular, and texture indices, this is a valid
+    /// starting point for struct update syntax.
+    pub fn principled_default() -> Material {
+        Material {
+            color: glam::Vec4::ONE,
+            roughness: 1.0,
+            reflectivity: 0.0,
             albedo_texture: INVALID_INDEX,
             mra_texture: INVALID_INDEX,
-            ..Default::default()
+            transmission: 0.0,
+            ior: Material::DEFAULT_IOR,
+            normal_texture: INVALID_INDEX,
+            normal_scale: 1.0,
+            sheen_color: glam::Vec3::ZERO,
+            sheen_roughness: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            anisotropy: 0.0,
+            anisotropy_rotation: 0.0,
+            specular_tint: 0.0,
+            dispersion: 0.0,
+            material_type: 0,
+            parameters: 0,
+            subsurface_color: glam::Vec3::ONE,
+            subsurface: 0.0,
+            subsurface_radius: glam::Vec3::new(1.0, 0.2, 0.1),
+            subsurface_scale: 0.05,
+            mode: MaterialMode::Surface as u32,
+            double_sided: 1,
+            back_material: INVALID_INDEX,
+            specular: 1.0,
+        }
+    }
+
+    /// Create a transmissive dielectric, e.g., glass or water.
+    pub fn dielectric(color: glam::Vec4, roughness: f32, ior: f32) -> Material {
+        Material {
+            transmission: 1.0,
+            ior,
+            ..Material::new(color, roughness, 0.0)
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: MaterialMode) {
+        self.mode = mode as u32;
+    }
+
+    pub fn mode(&self) -> MaterialMode {
+        match self.mode {
+            1 => MaterialMode::ShadowCatcher,
+            2 => MaterialMode::Holdout,
+            _ => MaterialMode::Surface,
+        }
+    }
+}
+
+impl Default for Material {
+    /// Zeroed color, roughness, reflectivity, specular, and texture indices.
+    ///
+    /// Other parameters disable their lobes, see
+    /// [`Material::principled_default`].
+    fn default() -> Material {
+        Material {
+            color: glam::Vec4::ZERO,
+            roughness: 0.0,
+            reflectivity: 0.0,
+            albedo_texture: 0,
+            mra_texture: 0,
+            specular: 0.0,
+            ..Material::principled_default()
         }
     }
 }
 
+/// Contribution of a material to the alpha, used to composite renders
+/// over photographs.
+///
+/// The alpha is accumulated alongside the radiance by the
+/// [`crate::passes::AccumulationPass`].
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaterialMode {
+    /// Opaque surface, shaded as usual.
+    #[default]
+    Surface = 0,
+    /// Transparent surface, only recording the shadows and reflections of
+    /// other objects, e.g., a ground plane matching the photograph.
+    ///
+    /// Seen from the camera, paths leaving the catcher toward the
+    /// environment are transparent, while paths blocked by other objects
+    /// are opaque and bring their radiance. Shadows thus behave like the
+    /// ambient occlusion of the environment. Seen indirectly, the catcher
+    /// is shaded as a regular surface.
+    ShadowCatcher = 1,
+    /// Cuts the alpha where the object is seen from the camera, e.g.,
+    /// for objects standing in for real ones of the photograph. Seen
+    /// indirectly, holdouts are black.
+    Holdout = 2,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
 pub struct Vertex {
+    /// Position in `xyz`, and first texture coordinate in `w`.
     pub position: [f32; 4],
-    pub normal: [f32; 4],
+    /// Shading attributes, packed to keep the vertex at 32 bytes:
+    /// - `x`: Octahedral-encoded normal
+    /// - `y`: Octahedral-encoded tangent
+    /// - `z`: Second texture coordinate, as float bits
+    /// - `w`: Tangent handedness, as float bits. `0.0` when no tangent is available
+    pub attributes: [u32; 4],
 }
 unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for Vertex {}
@@ -84,13 +316,47 @@ impl Vertex {
         let uv = uv.unwrap_or(&Self::DEFAULT_UV);
         Vertex {
             position: [position[0], position[1], position[2], uv[0]],
-            normal: [normal[0], normal[1], normal[2], uv[1]],
+            attributes: [pack_octahedral(*normal), 0, uv[1].to_bits(), 0],
         }
     }
 
     pub fn position(&self) -> &[f32; 3] {
         self.position[0..3].try_into().unwrap()
     }
+
+    pub fn set_position(&mut self, position: &[f32; 3]) {
+        self.position[0..3].copy_from_slice(position);
+    }
+
+    /// Unit normal, decoded from [`Vertex::attributes`].
+    pub fn normal(&self) -> [f32; 3] {
+        unpack_octahedral(self.attributes[0])
+    }
+
+    pub fn set_normal(&mut self, normal: &[f32; 3]) {
+        self.attributes[0] = pack_octahedral(*normal);
+    }
+
+    /// Tangent in `xyz`, and handedness of the bitangent in `w`.
+    pub fn tangent(&self) -> [f32; 4] {
+        let t = unpack_octahedral(self.attributes[1]);
+        [t[0], t[1], t[2], f32::from_bits(self.attributes[3])]
+    }
+
+    pub fn set_tangent(&mut self, tangent: &[f32; 4]) {
+        self.attributes[1] = pack_octahedral([tangent[0], tangent[1], tangent[2]]);
+        let handedness = if tangent[3] < 0.0 { -1.0_f32 } else { 1.0_f32 };
+        self.attributes[3] = handedness.to_bits();
+    }
+
+    pub fn uv(&self) -> [f32; 2] {
+        [self.position[3], f32::from_bits(self.attributes[2])]
+    }
+
+    pub fn set_uv(&mut self, uv: &[f32; 2]) {
+        self.position[3] = uv[0];
+        self.attributes[2] = uv[1].to_bits();
+    }
 }
 
 impl mesh::AsVertexFormat for Vertex {
@@ -102,7 +368,7 @@ impl mesh::AsVertexFormat for Vertex {
             },
             mesh::AttributeDescriptor {
                 id: mesh::AttributeId::NORMAL,
-                format: wgpu::VertexFormat::Float32x4,
+                format: wgpu::VertexFormat::Uint32x4,
             },
         ];
         &ATTRIBUTE_DESCRIPTORS
@@ -110,17 +376,37 @@ impl mesh::AsVertexFormat for Vertex {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy)]
 pub struct Light {
     pub normal: glam::Vec4,
     pub tangent: glam::Vec4,
     pub bitangent: glam::Vec4,
     pub intensity: f32,
-    padding_0: u32,
+    /// Row of the emission spectrum in the spectra texture, see
+    /// [`crate::spectrum::create_spectra_texture`].
+    ///
+    /// Only used in spectral mode, [`INVALID_INDEX`] for a white emitter.
+    /// Lights are only seen by passes created with the `AREA_LIGHTS` define,
+    /// see [`crate::passes::IntersectorPass::new_with_defines`].
+    pub spectrum: u32,
     padding_1: u32,
     padding_2: u32,
 }
 
+impl Default for Light {
+    fn default() -> Self {
+        Light {
+            normal: glam::Vec4::ZERO,
+            tangent: glam::Vec4::ZERO,
+            bitangent: glam::Vec4::ZERO,
+            intensity: 0.0,
+            spectrum: INVALID_INDEX,
+            padding_1: 0,
+            padding_2: 0,
+        }
+    }
+}
+
 unsafe impl bytemuck::Pod for Light {}
 unsafe impl bytemuck::Zeroable for Light {}
 impl Uniform for Light {}
@@ -175,7 +461,13 @@ pub struct PerDrawUniforms {
     pub frame_count: u32,
     pub seed: u32,
     pub bounces: u32,
-    pub padding: u32,
+    /// Maximum luminance of a sample, applied by the
+    /// [`crate::passes::AccumulationPass`], and to the light path AOVs by the
+    /// [`crate::passes::AovAccumulationPass`]. `0` disables the clamp.
+    ///
+    /// Removes fireflies at the cost of bias, i.e., bright highlights
+    /// and caustics lose energy.
+    pub sample_clamp: f32,
     pub dimensions: [u32; 2],
 }
 
@@ -191,17 +483,98 @@ unsafe impl bytemuck::Pod for PerDrawUniforms {}
 unsafe impl bytemuck::Zeroable for PerDrawUniforms {}
 impl Uniform for PerDrawUniforms {}
 
+/// Projection used to generate primary rays.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    #[default]
+    Perspective = 0,
+    /// Parallel rays, covering [`Camera::orthographic_height`] vertically.
+    Orthographic = 1,
+    /// 360° latitude-longitude panorama.
+    Equirectangular = 2,
+    /// Six cube faces laid out on a 3x2 grid: `+X`, `-X`, `+Y` on the top
+    /// row, and `-Y`, `+Z`, `-Z` on the bottom row.
+    Cubemap = 3,
+    /// Omni-directional stereo panorama, with the left eye on the top half
+    /// of the image and the right eye on the bottom half.
+    StereoEquirectangular = 4,
+}
+
+/// Pixel reconstruction filter.
+///
+/// Sub-pixel offsets are importance sampled according to the filter. Filters
+/// with negative lobes are handled by accumulating signed weights.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    Box = 0,
+    Tent = 1,
+    Gaussian = 2,
+    BlackmanHarris = 3,
+    /// Mitchell-Netravali, with `B = C = 1/3`.
+    Mitchell = 4,
+}
+
+impl Filter {
+    /// Radius, in pixels, commonly used with this filter.
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::BlackmanHarris => 2.0,
+            Filter::Mitchell => 2.0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct Camera {
     pub origin: glam::Vec3,
     pub v_fov: f32,
     pub up: glam::Vec3,
-    pub padding_0: f32,
+    /// Radius of the lens aperture, `0.0` for a pinhole camera.
+    pub aperture: f32,
     pub right: glam::Vec3,
-    pub padding_1: f32,
+    /// Distance to the plane in focus, along the view direction.
+    pub focus_distance: f32,
     pub dimensions: [u32; 2],
-    pub padding_2: [u32; 2],
+    /// Number of diaphragm blades, `0` for a circular aperture.
+    pub aperture_blades: u32,
+    /// Rotation of the diaphragm blades, in radians.
+    pub aperture_rotation: f32,
+    /// Ratio between the height and the width of the aperture.
+    ///
+    /// Anamorphic lenses produce bokeh stretched vertically, e.g., using `2.0`.
+    pub anamorphic_ratio: f32,
+    /// See [`Projection`].
+    pub projection: u32,
+    /// Height of the view, in world units, for [`Projection::Orthographic`].
+    pub orthographic_height: f32,
+    /// Distance between both eyes for [`Projection::StereoEquirectangular`].
+    pub interpupillary_distance: f32,
+    /// See [`Filter`].
+    pub filter: u32,
+    /// Radius of the reconstruction filter, in pixels.
+    ///
+    /// `0.0` disables sub-pixel jittering, and thus anti-aliasing.
+    pub filter_radius: f32,
+    /// Sensor sensitivity, used for the exposure, see [`Camera::exposure`].
+    pub iso: f32,
+    /// Exposure time, in seconds.
+    pub shutter_speed: f32,
+    /// Ratio of the focal length to the aperture diameter, only used for
+    /// the exposure.
+    ///
+    /// Independent of the lens [`Camera::aperture`], such that focusing
+    /// with [`Camera::set_f_stop`] doesn't change the brightness.
+    pub exposure_f_stop: f32,
+    /// Exposure offset, in stops.
+    pub exposure_compensation: f32,
+    pub padding: [u32; 2],
 }
 
 impl Camera {
@@ -211,11 +584,102 @@ impl Camera {
         self.origin = transform.w_axis.xyz();
     }
 
+    /// Set the aperture from physical lens parameters.
+    ///
+    /// The focal length is expressed in world units. Only the depth of field
+    /// changes, the exposure uses [`Camera::exposure_f_stop`].
+    pub fn set_f_stop(&mut self, f_stop: f32, focal_length: f32) {
+        self.aperture = 0.5 * focal_length / f_stop;
+    }
+
+    /// Exposure value at ISO 100, from the ISO, shutter speed, and f-stop.
+    pub fn ev100(&self) -> f32 {
+        (self.exposure_f_stop * self.exposure_f_stop / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    /// Scale applied to the radiance before tonemapping.
+    ///
+    /// Radiance isn't expressed in physical units, the exposure is thus
+    /// calibrated such that an EV100 of `0` leaves it unchanged, e.g.,
+    /// at ISO 100, f/1, and one second.
+    pub fn exposure(&self) -> f32 {
+        (self.exposure_compensation - self.ev100()).exp2()
+    }
+
+    /// Set the focus distance such that `point` is sharp.
+    pub fn focus_on(&mut self, point: glam::Vec3) {
+        let forward = self.up.cross(self.right).normalize();
+        self.focus_distance = (point - self.origin).dot(forward).max(0.0);
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection as u32;
+    }
+
+    pub fn projection(&self) -> Projection {
+        match self.projection {
+            1 => Projection::Orthographic,
+            2 => Projection::Equirectangular,
+            3 => Projection::Cubemap,
+            4 => Projection::StereoEquirectangular,
+            _ => Projection::Perspective,
+        }
+    }
+
+    /// Set the reconstruction filter, using its default radius.
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter as u32;
+        self.filter_radius = filter.default_radius();
+    }
+
+    pub fn filter(&self) -> Filter {
+        match self.filter {
+            1 => Filter::Tent,
+            2 => Filter::Gaussian,
+            3 => Filter::BlackmanHarris,
+            4 => Filter::Mitchell,
+            _ => Filter::Box,
+        }
+    }
+
     pub fn perspective(&self, near: f32, far: f32) -> glam::Mat4 {
         let aspect = self.dimensions[0] as f32 / self.dimensions[1] as f32;
         glam::Mat4::perspective_lh(self.v_fov, aspect, near, far)
     }
 
+    pub fn orthographic(&self, near: f32, far: f32) -> glam::Mat4 {
+        let aspect = self.dimensions[0] as f32 / self.dimensions[1] as f32;
+        let half_height = 0.5 * self.orthographic_height;
+        let half_width = half_height * aspect;
+        glam::Mat4::orthographic_lh(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            near,
+            far,
+        )
+    }
+
+    /// Projection matrix matching the ray generation.
+    ///
+    /// Returns `None` for panoramic projections, which aren't linear.
+    pub fn projection_matrix(&self, near: f32, far: f32) -> Option<glam::Mat4> {
+        match self.projection() {
+            Projection::Perspective => Some(self.perspective(near, far)),
+            Projection::Orthographic => Some(self.orthographic(near, far)),
+            _ => None,
+        }
+    }
+
+    /// World to screen matrix, e.g., used to compute motion vectors.
+    ///
+    /// Returns `None` for panoramic projections, which aren't linear.
+    pub fn world_to_screen(&self, near: f32, far: f32) -> Option<glam::Mat4> {
+        self.projection_matrix(near, far)
+            .map(|projection| projection * self.transform().inverse())
+    }
+
     pub fn transform(&self) -> glam::Mat4 {
         let dir = self.up.cross(self.right).normalize().extend(0.0);
         let rot = glam::Mat4::from_cols(
@@ -235,10 +699,22 @@ impl Default for Camera {
             v_fov: 0.78,
             up: glam::Vec3::new(0.0, 1.0, 0.0),
             right: glam::Vec3::new(1.0, 0.0, 0.0),
-            padding_0: 0.0,
-            padding_1: 0.0,
-            padding_2: [0, 0],
+            aperture: 0.0,
+            focus_distance: 2.0,
             dimensions: [1, 1],
+            aperture_blades: 0,
+            aperture_rotation: 0.0,
+            anamorphic_ratio: 1.0,
+            projection: Projection::Perspective as u32,
+            orthographic_height: 2.0,
+            interpupillary_distance: 0.064,
+            filter: Filter::Box as u32,
+            filter_radius: Filter::Box.default_radius(),
+            iso: 100.0,
+            shutter_speed: 1.0,
+            exposure_f_stop: 1.0,
+            exposure_compensation: 0.0,
+            padding: [0, 0],
         }
     }
 }
@@ -254,6 +730,11 @@ pub struct Ray {
     dir: glam::Vec4,
     radiance: glam::Vec4,
     terminated: [u32; 4],
+    /// Width of the ray cone at the origin, and spread angle.
+    cone: glam::Vec2,
+    /// Coverage of the sample, see [`MaterialMode`].
+    alpha: f32,
+    padding: u32,
 }
 unsafe impl bytemuck::Pod for Ray {}
 unsafe impl bytemuck::Zeroable for Ray {}
@@ -265,7 +746,10 @@ impl Ray {
             origin: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
             dir: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
             radiance: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
-            terminated: [0, 0, 0, 0],
+            terminated: [0, 0, INVALID_INDEX, 0],
+            cone: glam::Vec2::ZERO,
+            alpha: 1.0,
+            padding: 0,
         }
     }
 
@@ -274,13 +758,20 @@ impl Ray {
             origin: glam::Vec4::new(origin.x, origin.y, origin.z, 1.0),
             dir: glam::Vec4::new(direction.x, direction.y, direction.z, 1.0),
             radiance: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
-            terminated: [0, 0, 0, 0],
+            terminated: [0, 0, INVALID_INDEX, 0],
+            cone: glam::Vec2::ZERO,
+            alpha: 1.0,
+            padding: 0,
         }
     }
 
     pub fn throughput(&self) -> glam::Vec3 {
         glam::Vec3::new(self.origin.w, self.dir.w, self.radiance.w)
     }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
 }
 
 #[repr(C)]
@@ -292,7 +783,8 @@ pub struct Intersection {
     material_index: u32,
     emitter: u32,
     dist: f32,
-    padding_0: f32,
+    /// Index of the intersected ray, only used with ray compaction.
+    ray: u32,
 }
 
 unsafe impl bytemuck::Pod for Intersection {}
@@ -353,9 +845,182 @@ unsafe impl bytemuck::Zeroable for TextureInfo {}
 impl Uniform for TextureInfo {}
 
 #[repr(C)]
-#[derive(Clone, Copy, Default, Pod, Zeroable)]
+#[derive(Clone, Copy, Pod, Zeroable)]
 pub struct RadianceParameters {
     pub use_noise_texture: u32,
+    /// Number of bounces after which paths are randomly terminated,
+    /// based on their throughput. `u32::MAX`, the default, disables
+    /// Russian roulette.
+    pub russian_roulette_depth: u32,
+    /// Row of the environment spectrum in the spectra texture, scaled by
+    /// the luminance of the environment.
+    ///
+    /// Only used in spectral mode, [`INVALID_INDEX`] to upsample the
+    /// environment color instead.
+    pub environment_spectrum: u32,
+    /// Scale applied to the environment probe, `0.25` by default.
+    pub environment_intensity: f32,
+    /// Medium the camera is in, e.g., fog, or water for underwater scenes.
+    pub fog: Medium,
+    /// Maximum number of diffuse bounces, including sheen.
+    ///
+    /// **Note**: There is no light sampling, a depth of `0` thus
+    /// leaves diffuse surfaces black.
+    pub max_diffuse_depth: u32,
+    /// Maximum number of glossy bounces, including clearcoat.
+    pub max_glossy_depth: u32,
+    /// Maximum number of refractions.
+    pub max_transmission_depth: u32,
+    /// Maximum luminance brought by paths after their first bounce.
+    /// `0` disables the clamp.
+    ///
+    /// Trades bias for variance: fireflies from indirect paths, e.g.,
+    /// diffuse surfaces seeing a specular reflection of the sky, are
+    /// removed, but indirect lighting loses energy.
+    pub indirect_clamp: f32,
+    /// Minimum perceptual roughness of surfaces hit after a diffuse
+    /// bounce. `0` disables the regularization.
+    ///
+    /// Trades bias for variance: blurring glossy reflections seen
+    /// through diffuse bounces removes caustic fireflies, at the cost
+    /// of duller indirect highlights.
+    pub roughness_regularization: f32,
+    /// Set to `1` to keep the environment seen from the camera out of the
+    /// image, with a zero alpha. It still lights the scene.
+    pub transparent_background: u32,
+    pub padding_1: [u32; 2],
+}
+
+impl Default for RadianceParameters {
+    fn default() -> Self {
+        // The overall depth is bounded by the number of shading dispatches.
+        Self {
+            use_noise_texture: 0,
+            russian_roulette_depth: u32::MAX,
+            environment_spectrum: INVALID_INDEX,
+            environment_intensity: 0.25,
+            fog: Medium::default(),
+            max_diffuse_depth: u32::MAX,
+            max_glossy_depth: u32::MAX,
+            max_transmission_depth: u32::MAX,
+            indirect_clamp: 0.0,
+            roughness_regularization: 0.0,
+            transparent_background: 0,
+            padding_1: [0; 2],
+        }
+    }
+}
+
+/// Exposure applied before tonemapping, see [`crate::passes::BlitPass`] and
+/// [`crate::passes::BlitTexturePass`].
+///
+/// Either set from [`Camera::exposure`], or written by the
+/// [`crate::passes::AutoExposurePass`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct Exposure {
+    pub scale: f32,
+    /// Average luminance the automatic exposure adapted to, `0` if none.
+    pub average_luminance: f32,
+    pub padding: [u32; 2],
+}
+impl Uniform for Exposure {}
+
+impl Exposure {
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale,
+            average_luminance: 0.0,
+            padding: [0; 2],
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self::new(camera.exposure())
+    }
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// Parameters of the [`crate::passes::AutoExposurePass`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct AutoExposureParameters {
+    /// Luminance range of the histogram, in stops. Luminances outside
+    /// the range are clamped.
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    /// Exposure offset, in stops.
+    pub compensation: f32,
+    /// Speed at which the exposure adapts to the luminance, in `[0; 1]`.
+    ///
+    /// `1` adapts instantly, smaller values smooth the exposure over frames.
+    pub adaptation: f32,
+}
+impl Uniform for AutoExposureParameters {}
+
+impl AutoExposureParameters {
+    /// Parameters using the exposure compensation of `camera`.
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            compensation: camera.exposure_compensation,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for AutoExposureParameters {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -10.0,
+            max_log_luminance: 10.0,
+            compensation: 0.0,
+            adaptation: 1.0,
+        }
+    }
+}
+
+/// Parameters of the [`crate::passes::BloomPass`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct BloomParameters {
+    /// Radiance above which pixels bloom.
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold.
+    pub knee: f32,
+    /// Amount of bloom added to the radiance.
+    pub intensity: f32,
+    /// Spread of the bloom in `[0; 1]`, larger values favor the coarser
+    /// levels of the chain.
+    pub radius: f32,
+    /// Amount of glare, relative to the bloom.
+    pub glare_intensity: f32,
+    /// Length of the glare streaks, in half resolution pixels.
+    pub glare_length: f32,
+    /// Rotation of the glare streaks, in radians.
+    pub glare_rotation: f32,
+    /// Number of lines of the glare star, `0` disables the glare.
+    pub glare_streaks: u32,
+}
+impl Uniform for BloomParameters {}
+
+impl Default for BloomParameters {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.05,
+            radius: 0.75,
+            glare_intensity: 1.0,
+            glare_length: 32.0,
+            glare_rotation: 0.0,
+            glare_streaks: 0,
+        }
+    }
 }
 
 pub type BVHNode = tinybvh_rs::cwbvh::Node;
@@ -378,6 +1043,15 @@ pub struct DenoiseResources<'a> {
     pub motion: &'a wgpu::TextureView,
 }
 
+/// Tables used by the spectral mode, see [`crate::spectrum`].
+#[derive(Clone, Copy)]
+pub struct SpectralResources<'a> {
+    /// View of [`crate::spectrum::RgbToSpectrumTable::create_texture`].
+    pub rgb_to_spectrum: &'a wgpu::TextureView,
+    /// View of [`crate::spectrum::create_spectra_texture`].
+    pub spectra: &'a wgpu::TextureView,
+}
+
 impl<'a> DenoiseResources<'a> {
     pub fn pong(&self) -> DenoiseResources<'a> {
         Self {
@@ -0,0 +1,39 @@
+use albedo_backend::data::ShaderCache;
+use albedo_rtx::passes::ATrousPass;
+use albedo_rtx::shaders::AlbedoRtxShaderImports;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    if !adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+        return None;
+    }
+    let descriptor = wgpu::DeviceDescriptor {
+        required_features: wgpu::Features::PUSH_CONSTANTS,
+        required_limits: wgpu::Limits {
+            max_push_constant_size: 16,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+}
+
+#[test]
+fn a_trous_count_is_clamped() {
+    let Some((device, _)) = device() else {
+        eprintln!("a_trous_count_is_clamped: skipped, no adapter available");
+        return;
+    };
+    let mut processor = ShaderCache::new();
+    processor.add_embedded::<AlbedoRtxShaderImports>();
+    let mut pass = ATrousPass::new(&device, &processor);
+
+    pass.set_count(0);
+    assert_eq!(pass.count(), 1);
+    pass.set_count(5);
+    assert_eq!(pass.count(), 5);
+    // Larger counts would overflow the step size.
+    pass.set_count(u8::MAX);
+    assert_eq!(pass.count(), ATrousPass::MAX_COUNT);
+}
@@ -0,0 +1,84 @@
+use albedo_rtx::cryptomatte::{cryptomatte_hash, murmur3_32, CryptomatteManifest};
+
+#[test]
+fn cryptomatte_murmur3() {
+    // Reference outputs of the x86 32 bits variant, covering the tail lengths.
+    assert_eq!(murmur3_32(b"", 0), 0);
+    assert_eq!(murmur3_32(b"", 1), 0x514e28b7);
+    assert_eq!(murmur3_32(b"", 0xffffffff), 0x81f16f39);
+    assert_eq!(murmur3_32(&[0, 0, 0, 0], 0), 0x2362f9de);
+    assert_eq!(murmur3_32(b"a", 0x9747b28c), 0x7fa09ea6);
+    assert_eq!(murmur3_32(b"abc", 0), 0xb3dd93fa);
+    assert_eq!(murmur3_32(b"aaaa", 0x9747b28c), 0x5a97808a);
+    assert_eq!(murmur3_32(b"Hello, world!", 1234), 0xfaf6cdb3);
+    assert_eq!(
+        murmur3_32(b"The quick brown fox jumps over the lazy dog", 0),
+        0x2e4ff723
+    );
+}
+
+#[test]
+fn cryptomatte_name_hash() {
+    // Examples of the Cryptomatte specification.
+    assert_eq!(cryptomatte_hash("bunny"), 0x13851a76);
+    assert_eq!(f32::from_bits(cryptomatte_hash("bunny")), 3.3600013e-27);
+    assert_eq!(cryptomatte_hash("default"), 0x42c9679f);
+    assert_eq!(f32::from_bits(cryptomatte_hash("default")), 100.702385);
+
+    // Hashes are stored as floats, and must survive filtering.
+    for i in 0..10_000 {
+        let hash = f32::from_bits(cryptomatte_hash(&format!("object_{}", i)));
+        assert!(hash.is_normal(), "object_{} hashes to {:e}", i, hash);
+    }
+}
+
+#[test]
+fn cryptomatte_manifest() {
+    let (manifest, hashes) = CryptomatteManifest::from_names(&["default", "bunny", "bunny"]);
+    assert_eq!(hashes, [0x42c9679f, 0x13851a76, 0x13851a76]);
+    assert_eq!(manifest.len(), 2);
+    assert_eq!(manifest.hash("bunny"), Some(0x13851a76));
+    assert_eq!(manifest.hash("teapot"), None);
+    // Names are sorted, and escaped.
+    assert_eq!(
+        manifest.to_json(),
+        r#"{"bunny":"13851a76","default":"42c9679f"}"#
+    );
+
+    let mut manifest = CryptomatteManifest::new();
+    assert!(manifest.is_empty());
+    assert_eq!(manifest.to_json(), "{}");
+    manifest.insert("say \"hi\"\\\n");
+    assert_eq!(
+        manifest.to_json(),
+        format!(
+            r#"{{"say \"hi\"\\\u000a":"{:08x}"}}"#,
+            cryptomatte_hash("say \"hi\"\\\n")
+        )
+    );
+}
+
+#[test]
+fn cryptomatte_metadata() {
+    let (manifest, _) = CryptomatteManifest::from_names(&["bunny", "default"]);
+    let metadata = manifest.metadata("CryptoObject");
+    let key = &format!("{:08x}", murmur3_32(b"CryptoObject", 0))[..7];
+    let prefix = format!("cryptomatte/{}", key);
+    assert_eq!(
+        metadata,
+        [
+            (format!("{}/name", prefix), "CryptoObject".to_string()),
+            (format!("{}/hash", prefix), "MurmurHash3_32".to_string()),
+            (
+                format!("{}/conversion", prefix),
+                "uint32_to_float32".to_string()
+            ),
+            (
+                format!("{}/manifest", prefix),
+                r#"{"bunny":"13851a76","default":"42c9679f"}"#.to_string()
+            ),
+        ]
+    );
+    // Layers get distinct keys.
+    assert_ne!(metadata[0].0, manifest.metadata("CryptoMaterial")[0].0);
+}
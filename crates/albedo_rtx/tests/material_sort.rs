@@ -0,0 +1,95 @@
+use albedo_backend::gpu;
+use albedo_rtx::passes::MaterialSortPass;
+use albedo_rtx::uniforms::{Intersection, INVALID_INDEX};
+
+/// Intersection of `ray` with `material`, or a miss if `None`.
+fn intersection(ray: u32, material: Option<u32>) -> Intersection {
+    let instance = if material.is_some() { 0 } else { INVALID_INDEX };
+    let raw: [u32; 8] = [0, 0, 0, instance, material.unwrap_or(0), 0, 0, ray];
+    bytemuck::cast(raw)
+}
+
+fn ray(intersection: &Intersection) -> u32 {
+    let raw: [u32; 8] = bytemuck::cast(*intersection);
+    raw[7]
+}
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+/// Run the pass, and return the rays of the output intersections.
+fn sort(device: &wgpu::Device, queue: &wgpu::Queue, pass: &MaterialSortPass) -> Vec<u32> {
+    let intersections = gpu::Buffer::new_storage_with_data(
+        device,
+        &[
+            intersection(0, Some(2)),
+            intersection(1, None),
+            intersection(2, Some(0)),
+            intersection(3, Some(1)),
+        ],
+        None,
+    );
+    // One workgroup, followed by the count and indices of active rays.
+    let active_rays = gpu::Buffer::<u32>::new_storage_with_data(
+        device,
+        &[1, 1, 1, 4, 0, 1, 2, 3],
+        Some(gpu::BufferInitDescriptor::new(
+            None,
+            wgpu::BufferUsages::INDIRECT,
+        )),
+    );
+    let bins = MaterialSortPass::create_bins(device, 3);
+    let sorted = gpu::Buffer::<Intersection>::new_storage(
+        device,
+        4,
+        Some(gpu::BufferInitDescriptor::new(
+            None,
+            wgpu::BufferUsages::COPY_SRC,
+        )),
+    );
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: sorted.inner().size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let bind_group = pass.create_frame_bind_groups(
+        device,
+        intersections.as_storage_slice().unwrap(),
+        active_rays.as_storage_slice().unwrap(),
+        bins.as_storage_slice().unwrap(),
+        sorted.as_storage_slice().unwrap(),
+    );
+    let mut encoder = device.create_command_encoder(&Default::default());
+    pass.dispatch(&mut encoder, &bind_group, &bins, &active_rays);
+    encoder.copy_buffer_to_buffer(sorted.inner(), 0, &readback, 0, readback.size());
+    queue.submit(Some(encoder.finish()));
+
+    readback.slice(..).map_async(wgpu::MapMode::Read, |_| ());
+    device.poll(wgpu::Maintain::Wait);
+    let data = readback.slice(..).get_mapped_range();
+    bytemuck::cast_slice::<u8, Intersection>(&data)
+        .iter()
+        .map(ray)
+        .collect()
+}
+
+#[test]
+fn material_sort_toggle() {
+    let Some((device, queue)) = device() else {
+        eprintln!("material_sort_toggle: skipped, no adapter available");
+        return;
+    };
+    let mut pass = MaterialSortPass::new(&device, None);
+    assert!(pass.enabled());
+    // Sorted by material, misses last.
+    assert_eq!(sort(&device, &queue, &pass), [2, 3, 0, 1]);
+
+    pass.set_enabled(false);
+    assert!(!pass.enabled());
+    assert_eq!(sort(&device, &queue, &pass), [0, 1, 2, 3]);
+}
@@ -0,0 +1,111 @@
+use albedo_rtx::procedural::{
+    ProceduralNode, ProceduralTexture, ProceduralTextureError, ProceduralTextures,
+};
+use albedo_rtx::uniforms::INVALID_INDEX;
+use glam::{Vec2, Vec4};
+
+fn checker() -> ProceduralTexture {
+    let mut texture = ProceduralTexture::new();
+    let red = texture.add(ProceduralNode::Constant(Vec4::new(1.0, 0.0, 0.0, 1.0)));
+    texture.add(ProceduralNode::Checker {
+        a: Some(red),
+        b: None,
+        scale: 4.0,
+    });
+    texture
+}
+
+#[test]
+fn procedural_index_encoding() {
+    let mut textures = ProceduralTextures::new();
+    assert!(textures.is_empty());
+
+    // Flag, node count in bits 24-30, and first node in bits 0-23.
+    let first = textures.add(&checker()).unwrap();
+    assert_eq!(first, 0x80000000 | (2 << 24));
+    let mut gradient = ProceduralTexture::new();
+    gradient.add(ProceduralNode::Gradient {
+        a: None,
+        b: None,
+        direction: Vec2::X,
+        offset: 0.5,
+    });
+    let second = textures.add(&gradient).unwrap();
+    assert_eq!(second, 0x80000000 | (1 << 24) | 2);
+    assert_ne!(second & ProceduralTextures::TEXTURE_BIT, 0);
+}
+
+#[test]
+fn procedural_node_encoding() {
+    let mut textures = ProceduralTextures::new();
+    textures.add(&checker()).unwrap();
+    let mut noise = ProceduralTexture::new();
+    let a = noise.add(ProceduralNode::Constant(Vec4::ZERO));
+    let b = noise.add(ProceduralNode::Constant(Vec4::ONE));
+    let factor = noise.add(ProceduralNode::Noise {
+        a: None,
+        b: None,
+        scale: 2.0,
+        octaves: 3,
+    });
+    noise.add(ProceduralNode::Mix {
+        a: Some(a),
+        b: Some(b),
+        factor: Some(factor),
+        amount: 0.25,
+    });
+    textures.add(&noise).unwrap();
+
+    // Two texels per node, `(type, inputs)` relative to the first node of
+    // the texture, then the parameters as float bits.
+    let bits = |v: [f32; 4]| v.map(f32::to_bits);
+    assert_eq!(
+        textures.texels(),
+        [
+            [0, INVALID_INDEX, INVALID_INDEX, INVALID_INDEX],
+            bits([1.0, 0.0, 0.0, 1.0]),
+            [1, 0, INVALID_INDEX, INVALID_INDEX],
+            bits([4.0, 0.0, 0.0, 0.0]),
+            [0, INVALID_INDEX, INVALID_INDEX, INVALID_INDEX],
+            bits([0.0; 4]),
+            [0, INVALID_INDEX, INVALID_INDEX, INVALID_INDEX],
+            bits([1.0; 4]),
+            [3, INVALID_INDEX, INVALID_INDEX, INVALID_INDEX],
+            bits([2.0, 3.0, 0.0, 0.0]),
+            [5, 0, 1, 2],
+            bits([0.25, 0.0, 0.0, 0.0]),
+        ]
+    );
+}
+
+#[test]
+fn procedural_node_limit() {
+    assert_eq!(ProceduralTextures::new().max_nodes(), 8192 / 2);
+    assert_eq!(
+        ProceduralTextures::with_max_texture_dimension(u32::MAX).max_nodes(),
+        ProceduralTextures::MAX_ENCODED_NODES
+    );
+
+    // The node texture fits two checkers, i.e., four nodes.
+    let mut textures = ProceduralTextures::with_max_texture_dimension(9);
+    assert_eq!(textures.max_nodes(), 4);
+    textures.add(&checker()).unwrap();
+    textures.add(&checker()).unwrap();
+    assert_eq!(
+        textures.add(&checker()),
+        Err(ProceduralTextureError::TooManyNodes { max_nodes: 4 })
+    );
+    // Failed additions leave the textures untouched.
+    assert_eq!(textures.texels().len(), 8);
+}
+
+#[test]
+#[should_panic]
+fn procedural_foreign_input() {
+    let mut texture = ProceduralTexture::new();
+    let other = checker().add(ProceduralNode::Constant(Vec4::ONE));
+    texture.add(ProceduralNode::Multiply {
+        a: Some(other),
+        b: None,
+    });
+}
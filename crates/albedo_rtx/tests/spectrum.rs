@@ -0,0 +1,116 @@
+use albedo_rtx::spectrum::{RgbToSpectrumTable, Spectrum, LAMBDA_MAX, LAMBDA_MIN};
+use albedo_rtx::uniforms::{Light, INVALID_INDEX};
+use glam::Vec3;
+
+fn assert_close(name: &str, value: Vec3, expected: Vec3, tolerance: f32) {
+    assert!(
+        (value - expected).abs().max_element() <= tolerance,
+        "{}: expected {:?}, got {:?}",
+        name,
+        expected,
+        value
+    );
+}
+
+fn chromaticity(spectrum: &Spectrum) -> (f32, f32) {
+    let xyz = spectrum.to_xyz();
+    let sum = xyz.x + xyz.y + xyz.z;
+    (xyz.x / sum, xyz.y / sum)
+}
+
+#[test]
+fn spectrum_white_round_trip() {
+    let table = RgbToSpectrumTable::new(2);
+    for value in [1.0, 0.5, 0.18] {
+        let rgb = Vec3::splat(value);
+        // Greys upsample to constant spectra.
+        for lambda in (380..=780).step_by(10) {
+            let sample = table.eval(rgb, lambda as f32);
+            assert!(
+                (sample - value).abs() < 1e-4,
+                "grey {} evaluates to {} at {}nm",
+                value,
+                sample,
+                lambda
+            );
+        }
+        let spectrum = Spectrum::from_measured(&[
+            (LAMBDA_MIN, table.eval(rgb, LAMBDA_MIN)),
+            (LAMBDA_MAX, table.eval(rgb, LAMBDA_MAX)),
+        ]);
+        assert_close("grey", spectrum.to_rgb(), rgb, 0.01);
+    }
+    // Equal-energy white point.
+    assert_close(
+        "constant",
+        Spectrum::constant(1.0).to_rgb(),
+        Vec3::ONE,
+        0.01,
+    );
+}
+
+#[test]
+fn spectrum_color_round_trip() {
+    let table = RgbToSpectrumTable::new(16);
+    for rgb in [
+        Vec3::new(0.8, 0.2, 0.1),
+        Vec3::new(0.1, 0.6, 0.3),
+        Vec3::new(0.2, 0.3, 0.9),
+    ] {
+        let mut spectrum = Spectrum::constant(0.0);
+        for (i, sample) in spectrum.samples.iter_mut().enumerate() {
+            *sample = table.eval(rgb, Spectrum::wavelength(i));
+        }
+        assert_close("color", spectrum.to_rgb(), rgb, 0.01);
+    }
+}
+
+#[test]
+fn spectrum_blackbody() {
+    // Peak normalized, with Wien's peak at ~580nm for 5000K.
+    let spectrum = Spectrum::blackbody(5000.0);
+    let max = spectrum.samples.iter().cloned().fold(0.0, f32::max);
+    assert!((max - 1.0).abs() < 1e-3, "peak {}", max);
+    assert!((spectrum.eval(580.0) - 1.0).abs() < 1e-3);
+
+    // CIE illuminant A, and the Planckian locus at 6500K.
+    let (x, y) = chromaticity(&Spectrum::blackbody(2856.0));
+    assert!(
+        (x - 0.4476).abs() < 0.005 && (y - 0.4074).abs() < 0.005,
+        "2856K: ({}, {})",
+        x,
+        y
+    );
+    let (x, y) = chromaticity(&Spectrum::blackbody(6500.0));
+    assert!(
+        (x - 0.3135).abs() < 0.005 && (y - 0.3236).abs() < 0.005,
+        "6500K: ({}, {})",
+        x,
+        y
+    );
+
+    // Warm emitters are red, hot emitters are blue.
+    let warm = Spectrum::blackbody(2000.0).to_rgb();
+    assert!(warm.x > warm.y && warm.y > warm.z, "2000K: {:?}", warm);
+    let hot = Spectrum::blackbody(15000.0).to_rgb();
+    assert!(hot.z > hot.y && hot.y > hot.x, "15000K: {:?}", hot);
+}
+
+#[test]
+fn spectrum_from_measured() {
+    let spectrum = Spectrum::from_measured(&[(400.0, 1.0), (500.0, 3.0), (600.0, 2.0)]);
+    // Clamped outside of the measurements, and linearly interpolated within.
+    assert_eq!(spectrum.eval(380.0), 1.0);
+    assert_eq!(spectrum.eval(780.0), 2.0);
+    assert!((spectrum.eval(450.0) - 2.0).abs() < 1e-5);
+    assert!((spectrum.eval(550.0) - 2.5).abs() < 1e-5);
+
+    let normalized = Spectrum::constant(3.0).normalized();
+    assert!((normalized.to_xyz().y - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn light_default_spectrum() {
+    assert_eq!(Light::default().spectrum, INVALID_INDEX);
+    assert_eq!(Light::new().spectrum, INVALID_INDEX);
+}
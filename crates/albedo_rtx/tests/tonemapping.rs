@@ -0,0 +1,126 @@
+use albedo_rtx::tonemapping::{
+    display, linear_to_srgb, tonemap, Tonemapper, TonemappingParameters,
+};
+use glam::{Vec3, Vec4};
+
+const TOLERANCE: f32 = 1e-3;
+
+fn assert_tonemap(tonemapper: Tonemapper, color: Vec3, expected: Vec3) {
+    let value = tonemap(color, &TonemappingParameters::new(tonemapper));
+    assert!(
+        (value - expected).abs().max_element() < TOLERANCE,
+        "{:?}: {:?} maps to {:?}, expected {:?}",
+        tonemapper,
+        color,
+        value,
+        expected
+    );
+}
+
+#[test]
+fn tonemapping_default() {
+    // The baseline curve of the blit passes.
+    assert_eq!(Tonemapper::default(), Tonemapper::AcesFilm);
+    let parameters = TonemappingParameters::default();
+    assert_eq!(parameters.tonemapper(), Tonemapper::AcesFilm);
+    assert_eq!(parameters.white_point, 1.0);
+    assert_eq!(parameters.contrast, 1.0);
+}
+
+#[test]
+fn tonemapping_operators() {
+    let grey = Vec3::splat(0.18);
+    let color = Vec3::new(0.8, 0.2, 0.05);
+
+    assert_tonemap(
+        Tonemapper::None,
+        Vec3::new(0.5, 2.0, -1.0),
+        Vec3::new(0.5, 1.0, 0.0),
+    );
+    // Extended Reinhard is the identity for a white point of `1`.
+    assert_tonemap(Tonemapper::Reinhard, color, color);
+    assert_tonemap(Tonemapper::Reinhard, Vec3::ONE, Vec3::ONE);
+
+    // Narkowicz, `x (2.51 x + 0.03) / (x (2.43 x + 0.59) + 0.14)`.
+    assert_tonemap(Tonemapper::AcesFilm, Vec3::ZERO, Vec3::ZERO);
+    assert_tonemap(Tonemapper::AcesFilm, grey, Vec3::splat(0.266899));
+    assert_tonemap(Tonemapper::AcesFilm, Vec3::ONE, Vec3::splat(0.803797));
+    assert_tonemap(Tonemapper::AcesFilm, Vec3::splat(100.0), Vec3::ONE);
+
+    // Hill, whose transforms leave greys grey.
+    assert_tonemap(Tonemapper::AcesFitted, grey, Vec3::splat(0.105593));
+    assert_tonemap(Tonemapper::AcesFitted, Vec3::ONE, Vec3::splat(0.619115));
+
+    assert_tonemap(
+        Tonemapper::AgX,
+        grey,
+        Vec3::new(0.214467, 0.214533, 0.214537),
+    );
+    assert_tonemap(
+        Tonemapper::AgX,
+        Vec3::ONE,
+        Vec3::new(0.589977, 0.590207, 0.590221),
+    );
+    assert_tonemap(
+        Tonemapper::AgX,
+        color,
+        Vec3::new(0.588917, 0.248606, 0.098592),
+    );
+
+    // Offset below the compression, then compressed toward the new peak.
+    assert_tonemap(
+        Tonemapper::PbrNeutral,
+        Vec3::new(0.5, 0.3, 0.2),
+        Vec3::new(0.46, 0.26, 0.16),
+    );
+    assert_tonemap(Tonemapper::PbrNeutral, Vec3::ONE, Vec3::splat(0.869091));
+}
+
+#[test]
+fn tonemapping_parameters() {
+    let mut parameters = TonemappingParameters::new(Tonemapper::None);
+    parameters.white_point = 2.0;
+    assert_eq!(tonemap(Vec3::ONE, &parameters), Vec3::splat(0.5));
+
+    // Reinhard maps the white point to `1`.
+    parameters.set_tonemapper(Tonemapper::Reinhard);
+    parameters.white_point = 4.0;
+    assert!((tonemap(Vec3::ONE, &parameters) - Vec3::splat(0.53125)).length() < TOLERANCE);
+    assert!((tonemap(Vec3::splat(4.0), &parameters) - Vec3::ONE).length() < TOLERANCE);
+
+    // Contrast pivots around middle grey.
+    let mut parameters = TonemappingParameters::new(Tonemapper::None);
+    parameters.contrast = 2.0;
+    assert!((tonemap(Vec3::splat(0.18), &parameters) - Vec3::splat(0.18)).length() < TOLERANCE);
+    assert!((tonemap(Vec3::splat(0.09), &parameters) - Vec3::splat(0.045)).length() < TOLERANCE);
+}
+
+#[test]
+fn tonemapping_srgb() {
+    assert_eq!(linear_to_srgb(Vec3::ZERO), Vec3::ZERO);
+    // Linear segment, continuous with the power segment.
+    assert!((linear_to_srgb(Vec3::splat(0.0031308)).x - 0.04045).abs() < 1e-4);
+    assert!((linear_to_srgb(Vec3::splat(0.5)).x - 0.735357).abs() < TOLERANCE);
+    assert!((linear_to_srgb(Vec3::ONE).x - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn tonemapping_display() {
+    let parameters = TonemappingParameters::new(Tonemapper::None);
+    // Four opaque samples of `0.5`.
+    assert_eq!(
+        display(Vec4::new(2.0, 2.0, 2.0, 4.0), 4.0, 1.0, &parameters),
+        [188, 188, 188, 255]
+    );
+    // Same radiance, premultiplied by a half coverage.
+    assert_eq!(
+        display(Vec4::new(1.0, 1.0, 1.0, 4.0), 2.0, 1.0, &parameters),
+        [188, 188, 188, 128]
+    );
+    // Exposure is applied before tonemapping.
+    assert_eq!(
+        display(Vec4::new(1.0, 1.0, 1.0, 4.0), 4.0, 2.0, &parameters),
+        [188, 188, 188, 255]
+    );
+    assert_eq!(display(Vec4::ZERO, 0.0, 1.0, &parameters), [0, 0, 0, 0]);
+}
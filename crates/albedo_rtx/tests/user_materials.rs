@@ -0,0 +1,54 @@
+use albedo_backend::data::ShaderCache;
+use albedo_rtx::{AlbedoRtxShaderImports, UserMaterials};
+use wgpu::naga::{self, FastHashMap};
+
+const LAMBERT: &str = r#"
+vec3
+lambert_evaluate(const UserSurface surface, const vec3 wo, const vec3 wi)
+{
+  return surface.baseColor * max(0.0, dot(surface.normal, wi)) / PI_F;
+}
+
+vec3
+lambert_sample(const UserSurface surface, const vec3 wo, out float pdf, out uint lobe, inout uint seed)
+{
+  vec3 wi = randomSampleDiffuse_Lambert(surface.normal, seed);
+  pdf = max(0.0, dot(surface.normal, wi)) / PI_F;
+  lobe = LOBE_DIFFUSE;
+  return wi;
+}
+"#;
+
+#[test]
+fn user_materials_dispatch() {
+    let mut processor = ShaderCache::new();
+    processor.add_embedded::<AlbedoRtxShaderImports>();
+
+    let mut materials = UserMaterials::new();
+    assert_eq!(materials.register(&mut processor, "lambert", LAMBERT), 1);
+    assert_eq!(materials.register(&mut processor, "lambert", LAMBERT), 1);
+    assert_eq!(materials.material_type("lambert"), Some(1));
+    assert_eq!(materials.material_type("toon"), None);
+
+    let dispatch = processor.get(UserMaterials::IMPORT).unwrap();
+    assert!(dispatch.contains("case 1u: return lambert_evaluate(surface, wo, wi);"));
+    assert!(dispatch.contains("case 1u: return lambert_sample(surface, wo, pdf, lobe, seed);"));
+
+    // Shading samples a direction, and weights it by the evaluated BSDF.
+    let mut defines = FastHashMap::default();
+    materials.insert_defines(&mut defines);
+    for spectral in [false, true] {
+        if spectral {
+            defines.insert("SPECTRAL".into(), "".into());
+        }
+        let module = processor
+            .compile_compute(include_str!("../shaders/shading.comp"), Some(&defines))
+            .unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use albedo_rtx::bsdf::{sample_principled, Lobe, MaterialState};
+use albedo_rtx::subsurface::{random_walk_subsurface, subsurface_medium, SurfaceHit};
+use albedo_rtx::uniforms::Material;
+use glam::Vec3;
+
+const SAMPLES: u32 = 100_000;
+const TOLERANCE: f32 = 0.02;
+
+/// Closed shape the random walk runs in: a unit sphere, tangent to the
+/// shaded point at the origin.
+fn sphere_hit(position: Vec3, dir: Vec3) -> Option<SurfaceHit> {
+    let center = Vec3::new(0.0, 0.0, -1.0);
+    let oc = position - center;
+    let b = oc.dot(dir);
+    let c = oc.length_squared() - 1.0;
+    let t = -b + (b * b - c).max(0.0).sqrt();
+    Some((t, (position + t * dir - center).normalize()))
+}
+
+/// Estimate the directional albedo of a material, i.e., the ratio of energy
+/// reflected or transmitted for a white environment.
+fn directional_albedo(material: &Material, cos_theta: f32) -> Vec3 {
+    let mat = MaterialState::from_material(material, true);
+    let eta = 1.0 / mat.ior;
+    let v = Vec3::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta);
+
+    let medium = subsurface_medium(
+        material.subsurface_color,
+        material.subsurface_radius * material.subsurface_scale,
+    );
+
+    let mut seed = 0x9e3779b9_u32 ^ cos_theta.to_bits();
+    let mut sum = Vec3::ZERO;
+    for _ in 0..SAMPLES {
+        let (l, mut weight, lobe) = sample_principled(v, &mat, eta, &mut seed);
+        if lobe == Lobe::Diffuse && l.z < 0.0 {
+            weight *= match random_walk_subsurface(
+                &medium,
+                mat.ior,
+                Vec3::ZERO,
+                l,
+                sphere_hit,
+                &mut seed,
+            ) {
+                Some(exit) => exit.throughput,
+                None => Vec3::ZERO,
+            };
+        }
+        assert!(weight.is_finite(), "non finite weight: {:?}", weight);
+        sum += weight;
+    }
+    sum / SAMPLES as f32
+}
+
+fn furnace(name: &str, material: Material) {
+    for cos_theta in [0.05, 0.2, 0.5, 0.8, 1.0] {
+        let albedo = directional_albedo(&material, cos_theta);
+        assert!(
+            albedo.max_element() <= 1.0 + TOLERANCE,
+            "{}: energy gain {:?} at cos(theta) = {}",
+            name,
+            albedo,
+            cos_theta
+        );
+    }
+}
+
+/// Like [`furnace`], for white materials that must neither gain nor lose
+/// energy.
+///
+/// Single scattering microfacet lobes lose energy as they get rougher, these
+/// materials are thus smooth.
+fn furnace_lossless(name: &str, material: Material) {
+    furnace(name, material);
+    for cos_theta in [0.05, 0.2, 0.5, 0.8, 1.0] {
+        let albedo = directional_albedo(&material, cos_theta);
+        assert!(
+            albedo.min_element() >= 1.0 - TOLERANCE,
+            "{}: energy loss {:?} at cos(theta) = {}",
+            name,
+            albedo,
+            cos_theta
+        );
+    }
+}
+
+#[test]
+fn white_furnace_base() {
+    for roughness in [0.05, 0.5, 1.0] {
+        furnace("dielectric", Material::new(glam::Vec4::ONE, roughness, 0.0));
+        furnace("metal", Material::new(glam::Vec4::ONE, roughness, 1.0));
+        furnace(
+            "specular",
+            Material {
+                roughness,
+                ..Material::principled_default()
+            },
+        );
+        furnace(
+            "specular tint",
+            Material {
+                specular: 1.0,
+                specular_tint: 1.0,
+                ..Material::new(glam::Vec4::new(0.2, 0.9, 0.1, 1.0), roughness, 0.0)
+            },
+        );
+    }
+    furnace_lossless("smooth dielectric", Material::new(glam::Vec4::ONE, 0.05, 0.0));
+    furnace_lossless(
+        "smooth specular",
+        Material {
+            roughness: 0.05,
+            ..Material::principled_default()
+        },
+    );
+    furnace_lossless("smooth metal", Material::new(glam::Vec4::ONE, 0.05, 1.0));
+}
+
+#[test]
+fn white_furnace_anisotropy() {
+    for anisotropy in [0.5, 1.0] {
+        furnace(
+            "anisotropy",
+            Material {
+                anisotropy,
+                anisotropy_rotation: 0.7,
+                ..Material::new(glam::Vec4::ONE, 0.3, 1.0)
+            },
+        );
+    }
+}
+
+#[test]
+fn white_furnace_clearcoat() {
+    for clearcoat_roughness in [0.05, 0.5, 1.0] {
+        furnace(
+            "clearcoat",
+            Material {
+                clearcoat: 1.0,
+                clearcoat_roughness,
+                ..Material::new(glam::Vec4::ONE, 0.5, 0.0)
+            },
+        );
+    }
+}
+
+#[test]
+fn white_furnace_sheen() {
+    for sheen_roughness in [0.0, 0.5, 1.0] {
+        furnace(
+            "sheen",
+            Material {
+                sheen_color: Vec3::ONE,
+                sheen_roughness,
+                ..Material::new(glam::Vec4::ONE, 1.0, 0.0)
+            },
+        );
+        // The sheen is layered on top of the base, and must not hide it.
+        furnace_lossless(
+            "white sheen",
+            Material {
+                sheen_color: Vec3::ONE,
+                sheen_roughness,
+                roughness: 0.05,
+                ..Material::principled_default()
+            },
+        );
+    }
+}
+
+#[test]
+fn white_furnace_transmission() {
+    for roughness in [0.05, 0.5] {
+        furnace(
+            "transmission",
+            Material::dielectric(glam::Vec4::ONE, roughness, Material::DEFAULT_IOR),
+        );
+    }
+}
+
+#[test]
+fn white_furnace_layered() {
+    furnace(
+        "layered",
+        Material {
+            clearcoat: 1.0,
+            clearcoat_roughness: 0.1,
+            sheen_color: Vec3::ONE,
+            sheen_roughness: 0.3,
+            anisotropy: 0.8,
+            specular_tint: 0.5,
+            ..Material::new(glam::Vec4::ONE, 0.4, 0.5)
+        },
+    );
+}
+
+#[test]
+fn white_furnace_subsurface() {
+    for subsurface in [0.5, 1.0] {
+        furnace(
+            "subsurface",
+            Material {
+                subsurface,
+                ..Material::new(glam::Vec4::ONE, 0.5, 0.0)
+            },
+        );
+        // The walk is short enough to always leave the sphere, and the
+        // boundary reflects what it doesn't transmit.
+        furnace_lossless(
+            "white subsurface",
+            Material {
+                subsurface,
+                subsurface_radius: Vec3::ONE,
+                subsurface_scale: 0.2,
+                ..Material::new(glam::Vec4::ONE, 0.05, 0.0)
+            },
+        );
+    }
+}
+
+/// The subsurface color must be the albedo of the walk, unlike a
+/// diffuse transmission.
+#[test]
+fn subsurface_color() {
+    let material = Material {
+        subsurface: 1.0,
+        subsurface_color: Vec3::new(0.8, 0.4, 0.1),
+        subsurface_radius: Vec3::ONE,
+        subsurface_scale: 0.2,
+        ..Material::new(glam::Vec4::ONE, 0.05, 0.0)
+    };
+    let albedo = directional_albedo(&material, 1.0);
+    assert!(albedo.x > albedo.y && albedo.y > albedo.z, "{:?}", albedo);
+    assert!(albedo.z < 0.5, "{:?}", albedo);
+}
+
+/// A smooth white conductor must reflect all the energy it receives.
+#[test]
+fn white_furnace_conserves_metal() {
+    let albedo = directional_albedo(&Material::new(glam::Vec4::ONE, 0.05, 1.0), 1.0);
+    assert!(albedo.min_element() >= 1.0 - TOLERANCE, "{:?}", albedo);
+}
@@ -25,7 +25,7 @@ struct Uniforms {
 fn vs_main(
     @builtin(instance_index) idx : u32,
     @location(0) position: vec4<f32>,
-    @location(1) normal: vec4<f32>,
+    @location(1) attributes: vec4<u32>,
 ) -> VertexOutput {
     var result: VertexOutput;
     result.instance_index = idx;